@@ -0,0 +1,190 @@
+use std::{marker::PhantomData, time::Duration};
+
+use bevy::{ecs::system::{lifetimeless::SRes, SystemParam}, prelude::*, time::common_conditions::on_timer};
+use iyes_perf_ui::{prelude::*, utils::next_sort_key};
+
+use crate::{physics::Conservation, planets::{new_system, ForceLaws, Planet, PhysicsStep}, world::{Game, GameState}};
+
+pub struct DiagnosticsPlugin;
+
+impl Plugin for DiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<ConservationDiagnostics>()
+        // the potential energy needs every pair of planets, so it only gets measured a few times per second (instead of after every physics step),
+        // and right away when there is no start value
+        .add_systems(
+            FixedUpdate,
+            update_conservation_diagnostics.after(PhysicsStep).run_if(in_state(GameState::Running).and_then(on_timer(DIAGNOSTICS_INTERVAL).or_else(no_initial_diagnostics)))
+        )
+        // the drift is measured since the start of the run: everything that was edited while paused (planets, force law, G, ...) is part of the new start
+        .add_systems(OnEnter(GameState::Running), (restart_conservation_diagnostics, update_conservation_diagnostics).chain())
+        // planets that get added or removed while running (e.g. merged planets) also change all the quantities
+        .add_systems(Update, reset_conservation_diagnostics)
+        // entries for the FPS display
+        .add_perf_ui_entry_type::<PerfUiEntryEnergyDrift>()
+        .add_perf_ui_entry_type::<PerfUiEntryMomentumDrift>()
+        .add_perf_ui_entry_type::<PerfUiEntryAngularMomentumDrift>()
+        ;
+    }
+}
+
+// how often the diagnostics get measured (in fixed time, which is real time as long as the physics keeps up)
+const DIAGNOSTICS_INTERVAL: Duration = Duration::from_millis(250);
+
+// ############# Conservation Diagnostics Resource #############
+// energy, momentum and angular momentum of all planets, and how much they changed since the run started
+#[derive(Resource, Default)]
+pub struct ConservationDiagnostics {
+    // state at the start of the run (None until it is measured)
+    pub initial: Option<Conservation>,
+    pub current: Conservation,
+    // relative changes since the first measurement
+    pub energy_drift: f64,
+    pub momentum_drift: f64,
+    pub angular_momentum_drift: f64,
+}
+
+// ############# Systems #############
+fn update_conservation_diagnostics(
+    planet_q: Query<&Planet>,
+    game: Res<Game>,
+    force_laws: Res<ForceLaws>,
+    mut diagnostics: ResMut<ConservationDiagnostics>
+) {
+    let mut system = new_system(&game, &force_laws);
+    // [Note: the start velocity, when entering GameState::Running the initial velocity might not be added to the velocity yet]
+    for planet in planet_q.iter() {
+        let index = system.add_body(planet.position, planet.start_velocity(), planet.mass);
+        system.set_charge(index, planet.charge);
+    }
+    let current = system.conservation();
+
+    let initial = *diagnostics.initial.get_or_insert(current);
+    (diagnostics.energy_drift, diagnostics.momentum_drift, diagnostics.angular_momentum_drift) = current.drift(&initial);
+    diagnostics.current = current;
+}
+
+fn no_initial_diagnostics(diagnostics: Res<ConservationDiagnostics>) -> bool {
+    diagnostics.initial.is_none()
+}
+
+fn restart_conservation_diagnostics(mut diagnostics: ResMut<ConservationDiagnostics>) {
+    diagnostics.initial = None;
+}
+
+// a new or removed planet changes all the quantities, so it doesnt make sense to compare with the old start anymore
+// [Note: runs every frame, the removed planets can only be seen in the frame they got removed]
+fn reset_conservation_diagnostics(
+    added_q: Query<(), Added<Planet>>,
+    mut removed: RemovedComponents<Planet>,
+    mut diagnostics: ResMut<ConservationDiagnostics>
+) {
+    if !added_q.is_empty() || removed.read().next().is_some() {
+        diagnostics.initial = None;
+    }
+}
+
+// ############# FPS Display Entries #############
+// drifts above this get highlighted
+const DRIFT_HIGHLIGHT: f64 = 1e-3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConservedQuantity {
+    Energy,
+    Momentum,
+    AngularMomentum,
+}
+
+impl ConservedQuantity {
+    pub const ALL: [ConservedQuantity; 3] = [ConservedQuantity::Energy, ConservedQuantity::Momentum, ConservedQuantity::AngularMomentum];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConservedQuantity::Energy => "Energy",
+            ConservedQuantity::Momentum => "Momentum",
+            ConservedQuantity::AngularMomentum => "Angular Momentum",
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            ConservedQuantity::Energy => "E",
+            ConservedQuantity::Momentum => "P",
+            ConservedQuantity::AngularMomentum => "L",
+        }
+    }
+
+    pub fn drift(&self, diagnostics: &ConservationDiagnostics) -> f64 {
+        match self {
+            ConservedQuantity::Energy => diagnostics.energy_drift,
+            ConservedQuantity::Momentum => diagnostics.momentum_drift,
+            ConservedQuantity::AngularMomentum => diagnostics.angular_momentum_drift,
+        }
+    }
+}
+
+// picks the quantity of a PerfUiEntryDrift
+// [Note: the entries are components of the PerfUiRoot entity, so every entry needs its own type (the same type can only be added once)]
+pub trait DriftQuantity: Send + Sync + 'static {
+    const QUANTITY: ConservedQuantity;
+}
+
+pub struct Energy;
+pub struct Momentum;
+pub struct AngularMomentum;
+
+impl DriftQuantity for Energy {
+    const QUANTITY: ConservedQuantity = ConservedQuantity::Energy;
+}
+
+impl DriftQuantity for Momentum {
+    const QUANTITY: ConservedQuantity = ConservedQuantity::Momentum;
+}
+
+impl DriftQuantity for AngularMomentum {
+    const QUANTITY: ConservedQuantity = ConservedQuantity::AngularMomentum;
+}
+
+pub type PerfUiEntryEnergyDrift = PerfUiEntryDrift<Energy>;
+pub type PerfUiEntryMomentumDrift = PerfUiEntryDrift<Momentum>;
+pub type PerfUiEntryAngularMomentumDrift = PerfUiEntryDrift<AngularMomentum>;
+
+// relative change of one conserved quantity since the first measurement
+#[derive(Component)]
+pub struct PerfUiEntryDrift<Q: DriftQuantity> {
+    pub label: String,
+    pub sort_key: i32,
+    quantity: PhantomData<Q>,
+}
+
+impl<Q: DriftQuantity> Default for PerfUiEntryDrift<Q> {
+    fn default() -> Self {
+        Self { label: format!("{} Drift", Q::QUANTITY.name()), sort_key: next_sort_key(), quantity: PhantomData }
+    }
+}
+
+impl<Q: DriftQuantity> PerfUiEntry for PerfUiEntryDrift<Q> {
+    type Value = f64;
+    type SystemParam = SRes<ConservationDiagnostics>;
+
+    fn label(&self) -> &str {
+        &self.label
+    }
+
+    fn sort_key(&self) -> i32 {
+        self.sort_key
+    }
+
+    fn update_value(&self, diagnostics: &mut <Self::SystemParam as SystemParam>::Item<'_, '_>) -> Option<Self::Value> {
+        diagnostics.initial.map(|_| Q::QUANTITY.drift(diagnostics))
+    }
+
+    fn format_value(&self, value: &Self::Value) -> String {
+        format!("d{} {:.1e}", Q::QUANTITY.symbol(), value)
+    }
+
+    fn value_highlight(&self, value: &Self::Value) -> bool {
+        *value > DRIFT_HIGHLIGHT
+    }
+}
@@ -0,0 +1,396 @@
+use std::f64::consts::PI;
+
+use bevy::{math::DVec3, prelude::*, window::PrimaryWindow};
+use bevy_inspector_egui::bevy_egui::{egui::{self, DragValue, RichText}, EguiContext};
+
+use crate::{particles::Body, physics::{elements_to_state, orbit_velocity, BodyInputs, BodyProperties, Dimension, OrbitDirection, OrbitShape, OrbitalElements}, planets::{spawn_planet, Planet}, ui::{body_properties_ui, unit_drag_value, ACCENT_COLOR}, world::{Game, GameState}};
+
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<SystemEditor>()
+        .add_systems(Update, system_editor_window)
+        ;
+    }
+}
+
+// ############# Body Kind #############
+// only used for the default values of new bodies and to guess what they orbit
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BodyKind {
+    Star,
+    Planet,
+    Moon,
+}
+
+impl BodyKind {
+    fn name(&self) -> &'static str {
+        match self {
+            BodyKind::Star => "Star",
+            BodyKind::Planet => "Planet",
+            BodyKind::Moon => "Moon",
+        }
+    }
+
+    // radius and surface gravity of a new body (in simulation units)
+    fn default_size(&self) -> (f64, f64) {
+        match self {
+            BodyKind::Star => (300.0, 100.0),
+            BodyKind::Planet => (100.0, 10.0),
+            BodyKind::Moon => (30.0, 2.0),
+        }
+    }
+}
+
+// what a body orbits, the index always points to a body further up in the list
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum EditorParent {
+    // nothing, the body is placed relative to the center and doesnt move
+    Center,
+    Body(usize),
+    // center of mass of all bodies further up in the list (e.g. for planets around a double star)
+    Barycenter,
+}
+
+// ############# Editor Body #############
+#[derive(Clone, Debug)]
+struct EditorBody {
+    name: String,
+    kind: BodyKind,
+    inputs: BodyInputs,
+    mass: f64,
+    radius: f64,
+    parent: EditorParent,
+    // distance from the parent and the direction (in radians, 0 is to the right, counterclockwise)
+    distance: f64,
+    angle: f64,
+    // ignored for EditorParent::Center
+    shape: OrbitShape,
+    // place the body with orbital elements instead of distance, direction and shape
+    use_elements: bool,
+    elements: OrbitalElements,
+}
+
+// smallest semi-major axis that can be entered (in simulation units), with 0 the orbit would have no size and the velocity would be NaN
+const MIN_SEMI_MAJOR_AXIS: f64 = 1.0;
+
+impl EditorBody {
+    // elements of the orbit that distance, angle and shape describe (see orbit_velocity)
+    fn shape_elements(&self) -> OrbitalElements {
+        OrbitalElements {
+            semi_major_axis: (self.distance / (1.0 - self.shape.eccentricity)).max(MIN_SEMI_MAJOR_AXIS),
+            eccentricity: self.shape.eccentricity,
+            inclination: match self.shape.direction {
+                OrbitDirection::Prograde => self.shape.inclination,
+                OrbitDirection::Retrograde => PI - self.shape.inclination,
+            },
+            // the orbit gets tilted around the line to the parent, and the body starts at the periapsis
+            longitude_of_ascending_node: self.angle,
+            argument_of_periapsis: 0.0,
+            mean_anomaly: 0.0,
+        }
+    }
+}
+
+// ############# System Editor Resource #############
+// the list of bodies in the editor window, nothing gets spawned until "Create" is pressed
+#[derive(Resource)]
+pub struct SystemEditor {
+    pub open: bool,
+    bodies: Vec<EditorBody>,
+    // despawn all planets before creating the new ones
+    replace: bool,
+    // subtract the velocity of the center of mass, so the whole system doesnt drift away
+    remove_drift: bool,
+}
+
+impl Default for SystemEditor {
+    fn default() -> Self {
+        Self { open: false, bodies: Vec::new(), replace: true, remove_drift: true }
+    }
+}
+
+impl SystemEditor {
+    fn count(&self, kind: BodyKind) -> usize {
+        self.bodies.iter().filter(|body| body.kind == kind).count()
+    }
+
+    fn last(&self, kind: BodyKind) -> Option<usize> {
+        self.bodies.iter().rposition(|body| body.kind == kind)
+    }
+
+    // adds a body with default values that orbits what it most likely should orbit
+    fn add_body(&mut self, kind: BodyKind, gravitational_constant: f64) {
+        let stars = self.count(BodyKind::Star);
+        let planets = self.count(BodyKind::Planet);
+        let (parent, distance) = match kind {
+            // the first star sits in the center, all others orbit it
+            BodyKind::Star => match self.bodies.iter().position(|body| body.kind == BodyKind::Star) {
+                Some(first_star) => (EditorParent::Body(first_star), 1000.0),
+                None if self.bodies.is_empty() => (EditorParent::Center, 0.0),
+                None => (EditorParent::Barycenter, 1000.0),
+            },
+            // planets orbit the star (or all stars), further out with every planet
+            BodyKind::Planet => {
+                let distance = 4000.0 + 2500.0 * planets as f64;
+                match self.last(BodyKind::Star) {
+                    Some(_) if stars > 1 => (EditorParent::Barycenter, distance),
+                    Some(star) => (EditorParent::Body(star), distance),
+                    None if self.bodies.is_empty() => (EditorParent::Center, 0.0),
+                    None => (EditorParent::Barycenter, distance),
+                }
+            }
+            // moons orbit the last planet
+            BodyKind::Moon => match self.last(BodyKind::Planet).or(self.bodies.len().checked_sub(1)) {
+                Some(planet) => (EditorParent::Body(planet), 250.0),
+                None => (EditorParent::Center, 0.0),
+            },
+        };
+        let (radius, surface_gravity) = kind.default_size();
+        let properties = BodyProperties::from_inputs(BodyInputs::RadiusSurfaceGravity, radius, surface_gravity, gravitational_constant);
+        self.bodies.push(EditorBody {
+            name: format!("{} {}", kind.name(), self.count(kind) + 1),
+            kind,
+            inputs: BodyInputs::default(),
+            mass: properties.mass,
+            radius: properties.radius,
+            parent,
+            distance,
+            angle: 0.0,
+            shape: OrbitShape::default(),
+            use_elements: false,
+            elements: OrbitalElements::default(),
+        });
+    }
+
+    // bodies that orbited the removed one now orbit what it orbited
+    fn remove_body(&mut self, index: usize) {
+        let removed = self.bodies.remove(index);
+        for body in self.bodies.iter_mut() {
+            body.parent = match body.parent {
+                EditorParent::Body(parent) if parent == index => removed.parent,
+                EditorParent::Body(parent) if parent > index => EditorParent::Body(parent - 1),
+                parent => parent,
+            };
+        }
+    }
+}
+
+// position and velocity of every body: each one is placed relative to its parent, which is always placed before it
+fn place_bodies(bodies: &[EditorBody], gravitational_constant: f64) -> Vec<(DVec3, DVec3)> {
+    let mut placed: Vec<(DVec3, DVec3)> = Vec::with_capacity(bodies.len());
+    for body in bodies.iter() {
+        // position, velocity and mass of the parent
+        let parent = match body.parent {
+            EditorParent::Center => None,
+            EditorParent::Body(index) => Some((placed[index].0, placed[index].1, bodies[index].mass)),
+            EditorParent::Barycenter => {
+                let mass = bodies[..placed.len()].iter().map(|body| body.mass).sum::<f64>();
+                let (weighted_position, momentum) = placed.iter().zip(bodies.iter()).fold((DVec3::ZERO, DVec3::ZERO), |(position_sum, momentum), ((position, velocity), body)| {
+                    (position_sum + *position * body.mass, momentum + *velocity * body.mass)
+                });
+                if mass > 0.0 { Some((weighted_position / mass, momentum / mass, mass)) } else { None }
+            }
+        };
+        let (parent_position, parent_velocity, parent_mass) = parent.unwrap_or((DVec3::ZERO, DVec3::ZERO, 0.0));
+        if parent.is_some() && body.use_elements {
+            let (position, velocity) = elements_to_state(body.elements, gravitational_constant * (parent_mass + body.mass));
+            placed.push((parent_position + position, parent_velocity + velocity));
+            continue;
+        }
+        let position = parent_position + DVec3::new(body.angle.cos(), body.angle.sin(), 0.0) * body.distance;
+        let velocity = match parent {
+            Some(_) => orbit_velocity(position, parent_position, parent_velocity, parent_mass, body.mass, gravitational_constant, body.shape),
+            None => DVec3::ZERO,
+        };
+        placed.push((position, velocity));
+    }
+    placed
+}
+
+// ############# Editor Window #############
+fn system_editor_window(
+    mut commands: Commands,
+    mut egui_ctx: Query<&mut EguiContext, With<PrimaryWindow>>,
+    mut editor: ResMut<SystemEditor>,
+    mut game: ResMut<Game>,
+    asset_server: Res<AssetServer>,
+    body_q: Query<Entity, Body>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !editor.open {
+        return;
+    }
+    let units = game.units;
+    let gravitational_constant = units.system.gravitational_constant();
+    let editor = editor.as_mut();
+    let mut open = editor.open;
+    let mut create = false;
+
+    egui::Window::new("Solar System Editor")
+        .open(&mut open)
+        .default_width(350.0)
+        .show(egui_ctx.single_mut().get_mut(), |ui| {
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                let names = editor.bodies.iter().map(|body| body.name.clone()).collect::<Vec<_>>();
+                let mut removed = None;
+                for (index, body) in editor.bodies.iter_mut().enumerate() {
+                    ui.push_id(index, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label(RichText::new(body.kind.name()).color(ACCENT_COLOR));
+                            ui.text_edit_singleline(&mut body.name);
+                            if ui.button("Remove").clicked() {
+                                removed = Some(index);
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Orbits: ");
+                            let parent_name = match body.parent {
+                                EditorParent::Center => String::from("Nothing"),
+                                EditorParent::Body(parent) => names[parent].clone(),
+                                EditorParent::Barycenter => String::from("Barycenter"),
+                            };
+                            egui::ComboBox::from_id_source("parent")
+                                .selected_text(parent_name)
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut body.parent, EditorParent::Center, "Nothing");
+                                    // only bodies further up can be orbited, they get placed first
+                                    if index > 0 {
+                                        ui.selectable_value(&mut body.parent, EditorParent::Barycenter, "Barycenter");
+                                    }
+                                    for (parent, name) in names[..index].iter().enumerate() {
+                                        ui.selectable_value(&mut body.parent, EditorParent::Body(parent), name.as_str());
+                                    }
+                                });
+                        });
+                        let orbits = body.parent != EditorParent::Center;
+                        if orbits && ui.checkbox(&mut body.use_elements, "Orbital Elements").changed() && body.use_elements {
+                            // start with the orbit that was set up so far
+                            body.elements = body.shape_elements();
+                        }
+                        if orbits && body.use_elements {
+                            let elements = &mut body.elements;
+                            ui.horizontal(|ui| {
+                                ui.label("Semi-major Axis: ");
+                                if unit_drag_value(ui, true, &mut elements.semi_major_axis, Dimension::LENGTH, &units, "") {
+                                    elements.semi_major_axis = elements.semi_major_axis.max(MIN_SEMI_MAJOR_AXIS);
+                                }
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Eccentricity: ");
+                                ui.add(
+                                    DragValue::new(&mut elements.eccentricity)
+                                    .speed(0.01)
+                                    .clamp_range(0.0..=0.99)
+                                );
+                            });
+                            for (label, angle, max) in [
+                                ("Inclination: ", &mut elements.inclination, 180.0),
+                                ("Longitude of Ascending Node: ", &mut elements.longitude_of_ascending_node, 360.0),
+                                ("Argument of Periapsis: ", &mut elements.argument_of_periapsis, 360.0),
+                                ("Mean Anomaly: ", &mut elements.mean_anomaly, 360.0),
+                            ] {
+                                ui.horizontal(|ui| {
+                                    ui.label(label);
+                                    let mut degrees = angle.to_degrees();
+                                    if ui.add(DragValue::new(&mut degrees).speed(1.0).clamp_range(0.0..=max).suffix("°")).changed() {
+                                        *angle = degrees.to_radians();
+                                    }
+                                });
+                            }
+                        } else {
+                            ui.horizontal(|ui| {
+                                ui.label("Distance: ");
+                                if unit_drag_value(ui, true, &mut body.distance, Dimension::LENGTH, &units, "") {
+                                    body.distance = body.distance.max(0.0);
+                                }
+                                let mut degrees = body.angle.to_degrees();
+                                if ui.add(DragValue::new(&mut degrees).speed(1.0).clamp_range(0.0..=360.0).suffix("°")).changed() {
+                                    body.angle = degrees.to_radians();
+                                }
+                            });
+                            if orbits {
+                                ui.horizontal(|ui| {
+                                    ui.label("Eccentricity: ");
+                                    ui.add(
+                                        DragValue::new(&mut body.shape.eccentricity)
+                                        .speed(0.01)
+                                        .clamp_range(0.0..=0.99)
+                                    );
+                                    egui::ComboBox::from_id_source("direction")
+                                        .selected_text(body.shape.direction.name())
+                                        .show_ui(ui, |ui| {
+                                            for direction in OrbitDirection::ALL {
+                                                ui.selectable_value(&mut body.shape.direction, direction, direction.name());
+                                            }
+                                        });
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("Inclination: ");
+                                    let mut degrees = body.shape.inclination.to_degrees();
+                                    if ui.add(DragValue::new(&mut degrees).speed(1.0).clamp_range(0.0..=90.0).suffix("°")).changed() {
+                                        body.shape.inclination = degrees.to_radians();
+                                    }
+                                });
+                            }
+                        }
+                        let properties = BodyProperties::from_mass_radius(body.mass, body.radius, gravitational_constant);
+                        if let Some(properties) = body_properties_ui(ui, "inputs", &mut body.inputs, properties, &units) {
+                            body.mass = properties.mass;
+                            body.radius = properties.radius;
+                        }
+                        ui.separator();
+                    });
+                }
+                if let Some(index) = removed {
+                    editor.remove_body(index);
+                }
+
+                ui.horizontal(|ui| {
+                    for kind in [BodyKind::Star, BodyKind::Planet, BodyKind::Moon] {
+                        if ui.button(format!("Add {}", kind.name())).clicked() {
+                            editor.add_body(kind, gravitational_constant);
+                        }
+                    }
+                });
+                ui.checkbox(&mut editor.replace, "Replace existing planets and particles");
+                ui.checkbox(&mut editor.remove_drift, "Center of mass at rest");
+                create = ui.add_enabled(!editor.bodies.is_empty(), egui::Button::new("Create")).clicked();
+            });
+        });
+
+    if create {
+        // the test particles go too, they would orbit planets that dont exist anymore
+        if editor.replace {
+            for entity in body_q.iter() {
+                commands.entity(entity).despawn_recursive();
+            }
+            game.planet_count = 0;
+        }
+        let placed = place_bodies(&editor.bodies, gravitational_constant);
+        let drift = if editor.remove_drift {
+            let mass = editor.bodies.iter().map(|body| body.mass).sum::<f64>();
+            let momentum = placed.iter().zip(editor.bodies.iter()).map(|((_, velocity), body)| *velocity * body.mass).sum::<DVec3>();
+            if mass > 0.0 { momentum / mass } else { DVec3::ZERO }
+        } else {
+            DVec3::ZERO
+        };
+        // the velocities get applied when the simulation starts (see set_initial_velocity), so it gets paused
+        for (body, (position, velocity)) in editor.bodies.iter().zip(placed) {
+            let planet = Planet {
+                mass: body.mass,
+                position,
+                radius: body.radius,
+                initial_velocity: velocity - drift,
+                inputs: body.inputs,
+                ..default()
+            };
+            spawn_planet(&mut commands, asset_server.as_ref(), game.as_mut(), planet, Some(body.name.clone()));
+        }
+        next_state.set(GameState::Paused);
+        open = false;
+    }
+    editor.open = open;
+}
@@ -0,0 +1,11 @@
+// physics core: plain Rust, does not depend on the ECS, so it can be used (and tested) on its own
+pub mod physics;
+
+// bevy plugins that make up the app
+pub mod planets;
+pub mod world;
+pub mod ui;
+pub mod diagnostics;
+pub mod editor;
+pub mod particles;
+pub mod scenario;
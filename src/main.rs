@@ -6,31 +6,13 @@ use bevy::{app::AppExit, prelude::*, window::WindowMode};
 // used for the FPS counter
 use iyes_perf_ui::prelude::*;
 
-// import Planet logic
-mod planets;
-use planets::PlanetPlugin;
-
-mod world;
-use world::{WorldPlugin, GameState};
-
-mod ui;
-use ui::UiPlugin;
+// import Planet logic, world setup and User Interface from the library part of the crate
+use n_body_problem::{diagnostics::DiagnosticsPlugin, editor::EditorPlugin, particles::ParticlePlugin, planets::PlanetPlugin, scenario::ScenarioPlugin, world::{WorldPlugin, GameState}, ui::UiPlugin};
 
 /* 
 TODO: 
-    * Pfadvorhersagung für Planeten: Drwa Funktion evetuell fixed update -> 10Hz?
     * Hintergrund Shader
     * Shader für den Pfad der Planeten
-    * Speichern von aktuellen Planetenpositionen -geschwindigkeiten und -beschleunigungen (für speichern und laden von "Spielständen")
-    * genaue positionierung von planeten ermöglichen
-    * eventuell "sonnensystem editor": 
-        # automatische plazierung von planeten
-        # berechnung der geschwindigkeiten und beschleunigungen nach eingabe der 
-                                                                                * masse
-                                                                                * größe
-                                                                                * dichte
-                                                                                * entfernung
-                                                                                * flugrichtung(links,rechts)
     * Eigenes bild für Sonne
 */
 
@@ -54,10 +36,16 @@ fn main() {
         // .add_plugins(WorldInspectorPlugin::new())
         // load planet logic
         .add_plugins(PlanetPlugin)
+        // load massless test particles (rings, belts, debris)
+        .add_plugins(ParticlePlugin)
         // load world plugin (for setting up world [camera, background, etc.])
         .add_plugins(WorldPlugin)
         // load User Interface
         .add_plugins(UiPlugin)
+        // load solar system editor window (needs the UiPlugin for egui)
+        .add_plugins(EditorPlugin)
+        // load saving and loading of scenario files (the buttons are in the UI)
+        .add_plugins(ScenarioPlugin)
         // add system for gizmo config (line width, etc.)
         // load update systems where order is not important
         .add_systems(Update, (exit_listener,))
@@ -68,6 +56,8 @@ fn main() {
         // load Plugins for FPS monitoring
         .add_plugins(bevy::diagnostic::FrameTimeDiagnosticsPlugin)
         .add_plugins(PerfUiPlugin)
+        // load energy and momentum conservation diagnostics (needs the PerfUiPlugin for its entries)
+        .add_plugins(DiagnosticsPlugin)
 
 
 
@@ -0,0 +1,139 @@
+use bevy::{math::DVec3, prelude::*, render::{mesh::PrimitiveTopology, render_asset::RenderAssetUsages, view::NoFrustumCulling}, sprite::{MaterialMesh2dBundle, Mesh2dHandle}};
+use serde::{Deserialize, Serialize};
+
+use crate::{physics::{orbit_velocity, OrbitShape}, planets::Planet};
+
+pub struct ParticlePlugin;
+
+impl Plugin for ParticlePlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .add_systems(Startup, setup_particle_cloud)
+        .add_systems(Update, update_particle_cloud)
+        ;
+    }
+}
+
+// ############# Test Particle Component #############
+// massless body (rock of a ring or belt, debris): it feels the gravity of the planets, but doesnt attract anything
+// [Note: the particles get stepped together with the planets in update_physics, but they are not part of the collisions or the path prediction]
+#[derive(Component, Default)]
+pub struct TestParticle {
+    pub position: DVec3,
+    pub velocity: DVec3,
+}
+
+// query filter for everything that moves: planets and test particles (e.g. to remove all of them)
+pub(crate) type Body = Or<(With<Planet>, With<TestParticle>)>;
+
+// ############# Particle Cloud #############
+// all particles get drawn as points of one single mesh (a sprite per particle would be far too expensive)
+#[derive(Component)]
+struct ParticleCloud;
+
+// the mesh with the particle positions, the 2D and the 3D cloud both draw it
+#[derive(Resource)]
+struct ParticleMesh(Handle<Mesh>);
+
+const PARTICLE_COLOR: Color = Color::rgb(0.8, 0.75, 0.65);
+
+fn setup_particle_cloud(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut materials_3d: ResMut<Assets<StandardMaterial>>
+) {
+    // the mesh starts with one point, it gets replaced as soon as there are particles (and is hidden until then)
+    let mut mesh = Mesh::new(PrimitiveTopology::PointList, RenderAssetUsages::default());
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, vec![[0.0f32; 3]]);
+    let mesh = meshes.add(mesh);
+    commands.insert_resource(ParticleMesh(mesh.clone()));
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: Mesh2dHandle(mesh.clone()),
+            material: materials.add(ColorMaterial::from(PARTICLE_COLOR)),
+            // above the background, below the planets; the zero z scale flattens the cloud to the top-down view
+            transform: Transform::from_xyz(0.0, 0.0, -0.5).with_scale(Vec3::new(1.0, 1.0, 0.0)),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        // the bounds of the mesh would only be calculated once, so it would get culled as soon as the particles move away
+        NoFrustumCulling,
+        ParticleCloud,
+        Name::new("Particle Cloud"),
+    ));
+    // the 3D camera draws the same mesh with a 3D material
+    commands.spawn((
+        PbrBundle {
+            mesh,
+            material: materials_3d.add(StandardMaterial {
+                base_color: PARTICLE_COLOR,
+                unlit: true,
+                ..default()
+            }),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        NoFrustumCulling,
+        ParticleCloud,
+        Name::new("Particle Cloud 3D"),
+    ));
+}
+
+// writes the positions of all particles into the mesh
+fn update_particle_cloud(
+    particle_q: Query<&TestParticle>,
+    mut cloud_q: Query<&mut Visibility, With<ParticleCloud>>,
+    particle_mesh: Res<ParticleMesh>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut last_count: Local<usize>
+) {
+    let count = particle_q.iter().len();
+    // nothing changes while there are no particles
+    if count == 0 && *last_count == 0 {
+        return;
+    }
+    *last_count = count;
+    for mut visibility in cloud_q.iter_mut() {
+        *visibility = if count == 0 { Visibility::Hidden } else { Visibility::Visible };
+    }
+    if count == 0 {
+        return;
+    }
+    if let Some(mesh) = meshes.get_mut(&particle_mesh.0) {
+        let positions = particle_q.iter().map(|particle| particle.position.as_vec3().to_array()).collect::<Vec<_>>();
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    }
+}
+
+// ############# Belt Settings #############
+// ring of particles on circular orbits around a primary (position, velocity and mass)
+#[derive(Reflect, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct BeltSettings {
+    pub count: u32,
+    pub inner_radius: f64,
+    pub outer_radius: f64,
+}
+
+impl Default for BeltSettings {
+    fn default() -> Self {
+        Self { count: 2000, inner_radius: 1500.0, outer_radius: 2500.0 }
+    }
+}
+
+// spawns the particles evenly spread over the area of the ring
+pub(crate) fn spawn_belt(commands: &mut Commands, settings: BeltSettings, primary: (DVec3, DVec3, f64), gravitational_constant: f64) {
+    let (primary_position, primary_velocity, primary_mass) = primary;
+    // golden angle spiral: no random numbers needed and no visible pattern (the radius grows with the square root, so the density is even)
+    let golden_angle = std::f64::consts::PI * (3.0 - 5.0_f64.sqrt());
+    let (inner_squared, outer_squared) = (settings.inner_radius.powi(2), settings.outer_radius.powi(2));
+    let particles = (0..settings.count).map(|k| {
+        let fraction = (k as f64 + 0.5) / settings.count as f64;
+        let radius = (inner_squared + (outer_squared - inner_squared) * fraction).sqrt();
+        let angle = k as f64 * golden_angle;
+        let position = primary_position + DVec3::new(angle.cos(), angle.sin(), 0.0) * radius;
+        let velocity = orbit_velocity(position, primary_position, primary_velocity, primary_mass, 0.0, gravitational_constant, OrbitShape::default());
+        TestParticle { position, velocity }
+    }).collect::<Vec<_>>();
+    commands.spawn_batch(particles);
+}
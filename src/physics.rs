@@ -0,0 +1,355 @@
+use bevy::{math::DVec3, tasks::{ComputeTaskPool, ParallelSlice, TaskPool}};
+
+mod integrator;
+pub use integrator::Integrator;
+
+mod barnes_hut;
+pub use barnes_hut::{ForceSolver, Octree};
+
+mod collision;
+pub use collision::{bounce, collision_groups, find_collisions, merge_bodies, CollisionMode, MergedBody};
+
+mod diagnostics;
+pub use diagnostics::Conservation;
+
+mod body;
+pub use body::{BodyInputs, BodyProperties};
+
+mod units;
+pub use units::{Dimension, DisplayUnits, LengthUnit, MassUnit, TimeUnit, UnitSystem, Units};
+
+mod orbit;
+pub use orbit::{elements_to_state, orbit_velocity, state_to_elements, OrbitDirection, OrbitShape, OrbitalElements};
+
+mod force;
+pub use force::{softened_distance, Charged, ForceBody, ForceConstants, ForceLaw, ForceLawClone, Newtonian, PowerLaw, Yukawa};
+
+mod relativity;
+pub use relativity::post_newtonian_acceleration;
+
+// gravitational constant in SI units (see UnitSystem::gravitational_constant for the one used in the simulation)
+pub const G: f64 = 6.67430e-11;
+// speed of light in SI units (only used by the post-newtonian correction)
+pub const C: f64 = 299_792_458.0;
+
+// acceleration caused by a body with the given mass at offset delta (= position of the body - own position)
+// gravitational_constant: G in the units of the simulation
+// softening: plummer softening length, the distance gets replaced by sqrt(r² + softening²),
+// so the acceleration stays finite when two bodies get very close (and is zero instead of NaN at the same position)
+pub fn gravitational_acceleration(delta: DVec3, mass: f64, gravitational_constant: f64, softening: f64) -> DVec3 {
+    let sqr_dst = delta.length_squared() + softening * softening;
+    if sqr_dst > 0.0 {
+        delta * gravitational_constant * mass / (sqr_dst * sqr_dst.sqrt())
+    } else {
+        DVec3::ZERO
+    }
+}
+
+// below this number of bodies, splitting the work between threads costs more than it saves
+const PARALLEL_MIN_BODIES: usize = 64;
+
+// ############# N-Body System #############
+// holds the state of all bodies (positions, velocities and masses) and advances it in time
+// [Note: this is plain Rust on purpose, the ECS systems copy their state in here, step it and copy it back]
+#[derive(Clone, Debug)]
+pub struct NBodySystem {
+    pub positions: Vec<DVec3>,
+    pub velocities: Vec<DVec3>,
+    // bodies without mass are test particles: they feel the others, but dont attract anything
+    pub masses: Vec<f64>,
+    // only used by force laws with charges (set with set_charge)
+    pub charges: Vec<f64>,
+    // pinned bodies dont move, but still attract the others (set with pin)
+    // [Note: they act like an outside force, so momentum and angular momentum are not conserved anymore]
+    pub pinned: Vec<bool>,
+    // accelerations of the last step (only for displaying them, they get recalculated every step)
+    pub accelerations: Vec<DVec3>,
+    // numerical method used by step
+    pub integrator: Integrator,
+    // only used by adaptive integrators: allowed error per sub step (relative to the size of the positions and velocities)
+    pub tolerance: f64,
+    // only used by adaptive integrators: size of the last sub step, the next step starts with it (0 means it starts with the whole step)
+    pub adaptive_step: f64,
+    // only used by adaptive integrators: how many sub steps of the last step had to be repeated because the error was too big
+    pub rejected_steps: u32,
+    // how two bodies act on each other (newtonian gravity by default)
+    pub force_law: Box<dyn ForceLaw>,
+    // how the forces get calculated, and for barnes-hut the opening angle (size / distance below which a group of bodies counts as one)
+    pub force_solver: ForceSolver,
+    pub opening_angle: f64,
+    // calculates the forces on multiple threads (the result is exactly the same as on one thread)
+    pub parallel: bool,
+    // plummer softening length (see gravitational_acceleration)
+    pub softening: f64,
+    // G in the units of the simulation (see UnitSystem)
+    pub gravitational_constant: f64,
+    // adds the first order post-newtonian correction to the forces (see post_newtonian_acceleration), with c in the units of the simulation
+    pub post_newtonian: bool,
+    pub speed_of_light: f64,
+}
+
+impl Default for NBodySystem {
+    fn default() -> Self {
+        Self {
+            positions: Vec::new(),
+            velocities: Vec::new(),
+            masses: Vec::new(),
+            charges: Vec::new(),
+            pinned: Vec::new(),
+            accelerations: Vec::new(),
+            integrator: Integrator::default(),
+            // same as the default of the Game (with 0 every sub step would fail and shrink to the smallest size)
+            tolerance: 1e-5,
+            adaptive_step: 0.0,
+            rejected_steps: 0,
+            force_law: Box::new(Newtonian),
+            force_solver: ForceSolver::default(),
+            opening_angle: 0.0,
+            parallel: false,
+            softening: 0.0,
+            // SI units, if nothing else is set
+            gravitational_constant: G,
+            post_newtonian: false,
+            speed_of_light: C,
+        }
+    }
+}
+
+impl NBodySystem {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // adds a body to the system and returns its index
+    pub fn add_body(&mut self, position: DVec3, velocity: DVec3, mass: f64) -> usize {
+        self.positions.push(position);
+        self.velocities.push(velocity);
+        self.masses.push(mass);
+        self.charges.push(0.0);
+        self.pinned.push(false);
+        self.accelerations.push(DVec3::ZERO);
+        self.positions.len() - 1
+    }
+
+    // keeps the body at its position (its velocity gets set to zero on the next step)
+    pub fn pin(&mut self, index: usize) {
+        self.pinned[index] = true;
+    }
+
+    pub fn set_charge(&mut self, index: usize, charge: f64) {
+        self.charges[index] = charge;
+    }
+
+    // what the force law gets besides the bodies
+    pub fn force_constants(&self) -> ForceConstants {
+        ForceConstants { gravitational_constant: self.gravitational_constant, softening: self.softening }
+    }
+
+    fn force_body(&self, index: usize) -> ForceBody {
+        ForceBody { mass: self.masses[index], charge: self.charges[index] }
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    // calculates the acceleration of every body caused by all the other bodies (with the active force law)
+    pub fn compute_accelerations(&mut self) {
+        self.accelerations = self.accelerations_at(&self.positions, &self.velocities);
+    }
+
+    // same as compute_accelerations, but for the given state instead of the current one
+    // (integrators like runge-kutta need the accelerations at intermediate positions)
+    // [Note: the velocities are only needed for the post-newtonian correction; the kick-drift integrators pass the velocities they have
+    // at that moment (e.g. after the first half kick of velocity verlet), so for them the correction is only approximated]
+    pub fn accelerations_at(&self, positions: &[DVec3], velocities: &[DVec3]) -> Vec<DVec3> {
+        // only bodies with mass attract others, so massless test particles only cost one pass over the massive bodies each
+        let sources = (0..positions.len()).filter(|i| self.masses[*i] > 0.0).collect::<Vec<_>>();
+        let tree = match self.force_solver {
+            ForceSolver::Exact => None,
+            ForceSolver::BarnesHut => Some(Octree::new(
+                &sources.iter().map(|i| positions[*i]).collect::<Vec<_>>(),
+                &sources.iter().map(|i| self.masses[*i]).collect::<Vec<_>>(),
+                &sources.iter().map(|i| self.charges[*i]).collect::<Vec<_>>(),
+            )),
+        };
+        let constants = self.force_constants();
+        // position and properties of all sources in one list, so the force law can go through them in one call
+        let source_bodies = sources.iter().map(|j| (positions[*j], self.force_body(*j))).collect::<Vec<_>>();
+        // every body sums up its own acceleration (instead of applying each force to both bodies of a pair),
+        // this way the order of the additions doesnt depend on how the bodies are split between the threads
+        // pinned bodies have no acceleration, so with zero velocity every integrator leaves them where they are
+        let acceleration_of = |i: usize| {
+            if self.pinned[i] {
+                return DVec3::ZERO;
+            }
+            let newtonian = match &tree {
+                // the tree only knows the massive bodies, a massless one can never be found in it
+                Some(tree) => tree.acceleration(sources.binary_search(&i).unwrap_or(usize::MAX), positions[i], self.force_body(i), self.opening_angle, self.force_law.as_ref(), &constants),
+                None => self.exact_acceleration(i, positions[i], &sources, &source_bodies, &constants),
+            };
+            if self.post_newtonian {
+                newtonian + self.post_newtonian_correction(i, positions, velocities, &sources)
+            } else {
+                newtonian
+            }
+        };
+
+        if self.parallel && positions.len() >= PARALLEL_MIN_BODIES {
+            let indices = (0..positions.len()).collect::<Vec<_>>();
+            let task_pool = ComputeTaskPool::get_or_init(TaskPool::default);
+            indices.par_splat_map(task_pool, None, |chunk| chunk.iter().map(|i| acceleration_of(*i)).collect::<Vec<_>>())
+                .into_iter()
+                .flatten()
+                .collect()
+        } else {
+            (0..positions.len()).map(acceleration_of).collect()
+        }
+    }
+
+    // acceleration of body i caused by all the other (massive) bodies
+    fn exact_acceleration(&self, i: usize, position: DVec3, sources: &[usize], source_bodies: &[(DVec3, ForceBody)], constants: &ForceConstants) -> DVec3 {
+        // the body itself is not one of its own sources (the sources are sorted by index, so it can only be at one place)
+        let (before, after) = match sources.binary_search(&i) {
+            Ok(k) => (&source_bodies[..k], &source_bodies[k + 1..]),
+            Err(k) => (&source_bodies[..k], &source_bodies[k..]),
+        };
+        let body = self.force_body(i);
+        self.force_law.total_acceleration(position, body, before, constants) + self.force_law.total_acceleration(position, body, after, constants)
+    }
+
+    // post-newtonian correction of the acceleration of body i, caused by all the other (massive) bodies
+    // [Note: this is always summed up exactly, also with barnes-hut (the correction depends on the velocity of every single body)]
+    fn post_newtonian_correction(&self, i: usize, positions: &[DVec3], velocities: &[DVec3], sources: &[usize]) -> DVec3 {
+        let mut acceleration = DVec3::ZERO;
+        for j in sources.iter().copied() {
+            if j == i {
+                continue;
+            }
+            acceleration += post_newtonian_acceleration(positions[j] - positions[i], velocities[i] - velocities[j], self.masses[j], self.gravitational_constant, self.speed_of_light);
+        }
+        acceleration
+    }
+
+    // advances the system by dt with the selected integrator
+    pub fn step(&mut self, dt: f64) {
+        for (velocity, pinned) in self.velocities.iter_mut().zip(self.pinned.iter()) {
+            if *pinned {
+                *velocity = DVec3::ZERO;
+            }
+        }
+        match self.integrator {
+            Integrator::SymplecticEuler => self.step_symplectic_euler(dt),
+            Integrator::VelocityVerlet => self.step_velocity_verlet(dt),
+            Integrator::Leapfrog => self.step_leapfrog(dt),
+            Integrator::RungeKutta4 => self.step_runge_kutta4(dt),
+            Integrator::Yoshida4 => self.step_yoshida4(dt),
+            Integrator::DormandPrince45 => self.step_dormand_prince45(dt),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::TAU;
+
+    use super::*;
+
+    // two bodies with the same mass, 1 apart, on a circular orbit around their center of mass (with G = 1)
+    // [Note: the relative orbit has a = 1 and G (m1 + m2) = 1, so one period takes 2π]
+    fn circular_binary(integrator: Integrator) -> NBodySystem {
+        let mut system = NBodySystem { integrator, gravitational_constant: 1.0, ..NBodySystem::default() };
+        // the relative velocity is sqrt(G (m1 + m2) / r) = 1, each body has half of it
+        system.add_body(DVec3::new(-0.5, 0.0, 0.0), DVec3::new(0.0, -0.5, 0.0), 0.5);
+        system.add_body(DVec3::new(0.5, 0.0, 0.0), DVec3::new(0.0, 0.5, 0.0), 0.5);
+        system
+    }
+
+    #[test]
+    fn circular_orbit_period_and_energy() {
+        let mut system = circular_binary(Integrator::VelocityVerlet);
+        let energy = system.conservation().total_energy();
+        let steps = 2000;
+        let dt = TAU / steps as f64;
+
+        // after half a period the bodies have swapped their places
+        for _ in 0..steps / 2 {
+            system.step(dt);
+        }
+        assert!((system.positions[0] - DVec3::new(0.5, 0.0, 0.0)).length() < 1e-3, "{}", system.positions[0]);
+        assert!((system.positions[1] - DVec3::new(-0.5, 0.0, 0.0)).length() < 1e-3, "{}", system.positions[1]);
+
+        // and after a whole period they are back where they started
+        for _ in 0..steps / 2 {
+            system.step(dt);
+        }
+        assert!((system.positions[0] - DVec3::new(-0.5, 0.0, 0.0)).length() < 1e-3, "{}", system.positions[0]);
+        assert!((system.positions[1] - DVec3::new(0.5, 0.0, 0.0)).length() < 1e-3, "{}", system.positions[1]);
+        // the distance stays the same the whole time (it is a circle), and so does the energy
+        assert!(((system.positions[1] - system.positions[0]).length() - 1.0).abs() < 1e-5);
+        assert!(((system.conservation().total_energy() - energy) / energy).abs() < 1e-6);
+        // the center of mass doesnt move
+        assert!((system.positions[0] + system.positions[1]).length() < 1e-12);
+    }
+
+    #[test]
+    fn pinned_body_stays_and_attracts() {
+        for integrator in Integrator::ALL {
+            let mut system = NBodySystem { integrator, gravitational_constant: 1.0, ..NBodySystem::default() };
+            // the pinned body is much lighter and even has a velocity, it still must not move
+            let pinned_position = DVec3::new(0.3, -0.2, 0.1);
+            system.add_body(pinned_position, DVec3::new(0.5, 0.2, 0.0), 1.0);
+            system.pin(0);
+            let start = DVec3::new(2.0, 0.0, 0.0);
+            system.add_body(start, DVec3::ZERO, 10.0);
+            for _ in 0..50 {
+                system.step(0.01);
+            }
+            assert_eq!(system.positions[0], pinned_position, "{}", integrator.name());
+            assert_eq!(system.velocities[0], DVec3::ZERO, "{}", integrator.name());
+            // the other body falls towards it
+            let towards = pinned_position - start;
+            assert!((system.positions[1] - start).dot(towards) > 0.0, "{}", integrator.name());
+            assert!(system.velocities[1].dot(towards) > 0.0, "{}", integrator.name());
+        }
+    }
+
+    #[test]
+    fn parallel_is_the_same_as_serial() {
+        for force_solver in ForceSolver::ALL {
+            let system = |parallel: bool| {
+                let mut system = NBodySystem {
+                    integrator: Integrator::RungeKutta4,
+                    force_solver,
+                    opening_angle: 0.5,
+                    parallel,
+                    gravitational_constant: 1.0,
+                    softening: 0.1,
+                    post_newtonian: true,
+                    speed_of_light: 100.0,
+                    ..NBodySystem::default()
+                };
+                // more than PARALLEL_MIN_BODIES, and some massless ones in between
+                for k in 0..4 * PARALLEL_MIN_BODIES {
+                    let angle = k as f64 * 0.7;
+                    let radius = 10.0 + k as f64;
+                    let mass = if k % 5 == 0 { 0.0 } else { 1.0 + (k % 3) as f64 };
+                    system.add_body(DVec3::new(angle.cos(), angle.sin(), 0.01 * k as f64) * radius, DVec3::new(-angle.sin(), angle.cos(), 0.0) * 0.3, mass);
+                }
+                for _ in 0..5 {
+                    system.step(0.01);
+                }
+                system
+            };
+            let (parallel, serial) = (system(true), system(false));
+            // exactly the same, not only close
+            assert_eq!(parallel.accelerations, serial.accelerations, "{}", force_solver.name());
+            assert_eq!(parallel.positions, serial.positions, "{}", force_solver.name());
+            assert_eq!(parallel.velocities, serial.velocities, "{}", force_solver.name());
+        }
+    }
+}
@@ -0,0 +1,230 @@
+use bevy::{math::DVec3, reflect::Reflect};
+use serde::{Deserialize, Serialize};
+
+use super::{ForceBody, ForceConstants, ForceLaw};
+
+// ############# Force Solver #############
+// how the gravitational forces between the bodies get calculated
+#[derive(Reflect, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ForceSolver {
+    // every pair of bodies, O(n²)
+    #[default]
+    Exact,
+    // barnes-hut tree: far away groups of bodies get combined into one body at their center of mass, O(n log n)
+    BarnesHut,
+}
+
+impl ForceSolver {
+    pub const ALL: [ForceSolver; 2] = [ForceSolver::Exact, ForceSolver::BarnesHut];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ForceSolver::Exact => "Exact",
+            ForceSolver::BarnesHut => "Barnes-Hut",
+        }
+    }
+}
+
+// bodies at (nearly) the same position would be split forever, so below this depth they just share one leaf
+const MAX_DEPTH: u32 = 32;
+
+// ############# Octree #############
+// [Note: in 2D all bodies have the same z, so only 4 of the 8 children of a node are ever used and it works like a quadtree]
+pub struct Octree {
+    nodes: Vec<Node>,
+}
+
+struct Node {
+    // center and half of the edge length of the cube this node covers
+    center: DVec3,
+    half_size: f64,
+    // total mass of the bodies inside and the sum of mass * position (gets divided by the mass once the tree is built)
+    mass: f64,
+    center_of_mass: DVec3,
+    // total charge of the bodies inside (for force laws with charges)
+    // [Note: the charge of a group is placed at its center of mass, so with charges of both signs the tree is less accurate]
+    charge: f64,
+    // number of bodies inside
+    count: u32,
+    // index of the first of the 8 children (they are stored next to each other), None for leafs
+    children: Option<usize>,
+    // body stored in a leaf
+    body: Option<usize>,
+}
+
+impl Node {
+    fn new(center: DVec3, half_size: f64) -> Self {
+        Self { center, half_size, mass: 0.0, center_of_mass: DVec3::ZERO, charge: 0.0, count: 0, children: None, body: None }
+    }
+
+    // index (0..8) of the child that contains the position
+    fn octant(&self, position: DVec3) -> usize {
+        (position.x >= self.center.x) as usize
+            | ((position.y >= self.center.y) as usize) << 1
+            | ((position.z >= self.center.z) as usize) << 2
+    }
+}
+
+impl Octree {
+    pub fn new(positions: &[DVec3], masses: &[f64], charges: &[f64]) -> Self {
+        // the root is a cube around all bodies
+        let (min, max) = positions.iter().fold((DVec3::splat(f64::MAX), DVec3::splat(f64::MIN)), |(min, max), position| {
+            (min.min(*position), max.max(*position))
+        });
+        let half_size = if positions.is_empty() { 1.0 } else { ((max - min).max_element() / 2.0).max(f64::EPSILON) * 1.001 };
+        let center = if positions.is_empty() { DVec3::ZERO } else { (min + max) / 2.0 };
+
+        let mut tree = Self { nodes: vec![Node::new(center, half_size)] };
+        for body in 0..positions.len() {
+            tree.insert(body, positions, masses, charges);
+        }
+        for node in tree.nodes.iter_mut() {
+            if node.mass > 0.0 {
+                node.center_of_mass /= node.mass;
+            }
+        }
+        tree
+    }
+
+    fn insert(&mut self, body: usize, positions: &[DVec3], masses: &[f64], charges: &[f64]) {
+        let (position, mass, charge) = (positions[body], masses[body], charges[body]);
+        let mut index = 0;
+        let mut depth = 0;
+        loop {
+            // every node on the way down contains the body
+            let node = &mut self.nodes[index];
+            node.mass += mass;
+            node.center_of_mass += position * mass;
+            node.charge += charge;
+            node.count += 1;
+
+            match node.children {
+                Some(first_child) => {
+                    index = first_child + node.octant(position);
+                }
+                None if node.count == 1 => {
+                    // empty leaf, the body just gets stored here
+                    node.body = Some(body);
+                    return;
+                }
+                None if depth >= MAX_DEPTH => {
+                    // too deep, the body only adds its mass (and charge) to the leaf
+                    return;
+                }
+                None => {
+                    // leaf that already holds a body: split it and move the old body one level down
+                    let old_body = node.body.take().unwrap_or(body);
+                    let (old_position, old_mass, old_charge) = (positions[old_body], masses[old_body], charges[old_body]);
+                    let (center, half_size) = (node.center, node.half_size / 2.0);
+
+                    let first_child = self.nodes.len();
+                    self.nodes[index].children = Some(first_child);
+                    for octant in 0..8 {
+                        let offset = DVec3::new(
+                            if octant & 1 == 0 { -half_size } else { half_size },
+                            if octant & 2 == 0 { -half_size } else { half_size },
+                            if octant & 4 == 0 { -half_size } else { half_size },
+                        );
+                        self.nodes.push(Node::new(center + offset, half_size));
+                    }
+
+                    let old_octant = self.nodes[index].octant(old_position);
+                    let old_child = &mut self.nodes[first_child + old_octant];
+                    old_child.mass = old_mass;
+                    old_child.center_of_mass = old_position * old_mass;
+                    old_child.charge = old_charge;
+                    old_child.count = 1;
+                    old_child.body = Some(old_body);
+
+                    index = first_child + self.nodes[index].octant(position);
+                }
+            }
+            depth += 1;
+        }
+    }
+
+    // acceleration of the given body (index in the tree, position and what the force law needs to know about it)
+    // opening_angle: a node gets treated as one body if its size / distance is smaller than this (0 means exact)
+    // force_law and constants: see ForceLaw
+    pub fn acceleration(&self, body: usize, position: DVec3, force_body: ForceBody, opening_angle: f64, force_law: &dyn ForceLaw, constants: &ForceConstants) -> DVec3 {
+        let mut acceleration = DVec3::ZERO;
+        let mut stack = vec![0];
+
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if node.count == 0 || (node.count == 1 && node.body == Some(body)) {
+                continue;
+            }
+
+            let delta = node.center_of_mass - position;
+            let sqr_dst = delta.length_squared();
+            let inside = (position - node.center).abs().max_element() <= node.half_size;
+
+            match node.children {
+                // node is too close (or contains the body itself): go through its children
+                Some(first_child) if inside || (2.0 * node.half_size).powi(2) >= opening_angle.powi(2) * sqr_dst => {
+                    stack.extend(first_child..first_child + 8);
+                }
+                // node is far enough away or is a leaf: use its center of mass
+                _ => {
+                    acceleration += force_law.acceleration(delta, force_body, ForceBody { mass: node.mass, charge: node.charge }, constants);
+                }
+            }
+        }
+        acceleration
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::NBodySystem;
+    use super::*;
+
+    // cluster of bodies at pseudo random positions (a simple LCG, so the test always sees the same cluster)
+    // the last few bodies are placed exactly on top of others, those end up in leafs at MAX_DEPTH
+    fn cluster(force_solver: ForceSolver, opening_angle: f64) -> NBodySystem {
+        let mut system = NBodySystem { force_solver, opening_angle, gravitational_constant: 1.0, softening: 0.01, ..NBodySystem::default() };
+        let mut seed: u64 = 12345;
+        let mut random = || {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 11) as f64 / (1u64 << 53) as f64
+        };
+        for _ in 0..500 {
+            let position = DVec3::new(random(), random(), random()) * 2.0 - DVec3::ONE;
+            system.add_body(position, DVec3::ZERO, 0.5 + random());
+        }
+        for i in 0..10 {
+            let position = system.positions[i * 7];
+            system.add_body(position, DVec3::ZERO, 1.0);
+        }
+        system.compute_accelerations();
+        system
+    }
+
+    // error of every body, relative to the size of its exact acceleration
+    fn relative_errors(opening_angle: f64) -> Vec<f64> {
+        let exact = cluster(ForceSolver::Exact, 0.0);
+        let tree = cluster(ForceSolver::BarnesHut, opening_angle);
+        exact.accelerations.iter().zip(tree.accelerations.iter())
+            .map(|(exact, tree)| (*tree - *exact).length() / exact.length())
+            .collect()
+    }
+
+    #[test]
+    fn zero_opening_angle_is_exact() {
+        // only the order of the additions is different
+        let errors = relative_errors(0.0);
+        assert!(errors.iter().all(|error| *error < 1e-10), "{:?}", errors.iter().copied().fold(0.0, f64::max));
+    }
+
+    #[test]
+    fn opening_angle_error() {
+        // with 0.5 the error is below 1% on average, and below 10% for every single body
+        // [Note: the worst ones are close to the center, where the forces from all sides nearly cancel out]
+        let errors = relative_errors(0.5);
+        let mean = errors.iter().sum::<f64>() / errors.len() as f64;
+        let max = errors.iter().copied().fold(0.0, f64::max);
+        assert!(mean < 0.01, "mean error {mean}");
+        assert!(max < 0.1, "max error {max}");
+    }
+}
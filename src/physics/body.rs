@@ -0,0 +1,125 @@
+use std::f64::consts::PI;
+
+use bevy::reflect::Reflect;
+use serde::{Deserialize, Serialize};
+
+// ############# Body Inputs #############
+// which two of the four properties of a body are given, the other two get derived from them
+#[derive(Reflect, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BodyInputs {
+    #[default]
+    MassRadius,
+    MassDensity,
+    MassSurfaceGravity,
+    RadiusDensity,
+    RadiusSurfaceGravity,
+    DensitySurfaceGravity,
+}
+
+impl BodyInputs {
+    pub const ALL: [BodyInputs; 6] = [
+        BodyInputs::MassRadius,
+        BodyInputs::MassDensity,
+        BodyInputs::MassSurfaceGravity,
+        BodyInputs::RadiusDensity,
+        BodyInputs::RadiusSurfaceGravity,
+        BodyInputs::DensitySurfaceGravity,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BodyInputs::MassRadius => "Mass + Radius",
+            BodyInputs::MassDensity => "Mass + Density",
+            BodyInputs::MassSurfaceGravity => "Mass + Surface Gravity",
+            BodyInputs::RadiusDensity => "Radius + Density",
+            BodyInputs::RadiusSurfaceGravity => "Radius + Surface Gravity",
+            BodyInputs::DensitySurfaceGravity => "Density + Surface Gravity",
+        }
+    }
+
+    // whether the property is one of the two inputs: (mass, radius, density, surface gravity)
+    pub fn is_input(&self) -> (bool, bool, bool, bool) {
+        match self {
+            BodyInputs::MassRadius => (true, true, false, false),
+            BodyInputs::MassDensity => (true, false, true, false),
+            BodyInputs::MassSurfaceGravity => (true, false, false, true),
+            BodyInputs::RadiusDensity => (false, true, true, false),
+            BodyInputs::RadiusSurfaceGravity => (false, true, false, true),
+            BodyInputs::DensitySurfaceGravity => (false, false, true, true),
+        }
+    }
+}
+
+// ############# Body Properties #############
+// mass, size and what follows from them (the body is treated as a homogeneous sphere)
+// [Note: the simulation only uses mass and radius, density and surface gravity are derived;
+//  all values are in simulation units, so G has to be the one of the simulation (see UnitSystem)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BodyProperties {
+    pub mass: f64,
+    pub radius: f64,
+    pub density: f64,
+    pub surface_gravity: f64,
+}
+
+impl BodyProperties {
+    pub fn from_mass_radius(mass: f64, radius: f64, gravitational_constant: f64) -> Self {
+        let volume = 4.0 / 3.0 * PI * radius.powi(3);
+        Self {
+            mass,
+            radius,
+            density: if volume > 0.0 { mass / volume } else { 0.0 },
+            surface_gravity: if radius > 0.0 { gravitational_constant * mass / radius.powi(2) } else { 0.0 },
+        }
+    }
+
+    // derives all properties from the two given ones; the values are in the order of the names (e.g. density, surface gravity)
+    pub fn from_inputs(inputs: BodyInputs, first: f64, second: f64, gravitational_constant: f64) -> Self {
+        let (mass, radius) = match inputs {
+            BodyInputs::MassRadius => (first, second),
+            // density = mass / (4/3 * pi * r³)
+            BodyInputs::MassDensity => (first, if second > 0.0 { (3.0 * first / (4.0 * PI * second)).cbrt() } else { 0.0 }),
+            // surface gravity = G * mass / r²
+            BodyInputs::MassSurfaceGravity => (first, if second > 0.0 { (gravitational_constant * first / second).sqrt() } else { 0.0 }),
+            BodyInputs::RadiusDensity => (second * 4.0 / 3.0 * PI * first.powi(3), first),
+            BodyInputs::RadiusSurfaceGravity => (second * first.powi(2) / gravitational_constant, first),
+            // both combined: surface gravity = G * density * 4/3 * pi * r
+            BodyInputs::DensitySurfaceGravity => {
+                let radius = if first > 0.0 { 3.0 * second / (4.0 * PI * gravitational_constant * first) } else { 0.0 };
+                (first * 4.0 / 3.0 * PI * radius.powi(3), radius)
+            }
+        };
+        Self::from_mass_radius(mass, radius, gravitational_constant)
+    }
+
+    // the two values that are the inputs, in the same order as from_inputs takes them
+    pub fn inputs(&self, inputs: BodyInputs) -> (f64, f64) {
+        match inputs {
+            BodyInputs::MassRadius => (self.mass, self.radius),
+            BodyInputs::MassDensity => (self.mass, self.density),
+            BodyInputs::MassSurfaceGravity => (self.mass, self.surface_gravity),
+            BodyInputs::RadiusDensity => (self.radius, self.density),
+            BodyInputs::RadiusSurfaceGravity => (self.radius, self.surface_gravity),
+            BodyInputs::DensitySurfaceGravity => (self.density, self.surface_gravity),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inputs_round_trip() {
+        // the earth in SI units, and a planet in simulation units
+        for (mass, radius, gravitational_constant) in [(5.972e24, 6.371e6, 6.674e-11), (1.5e7, 100.0, 1.0)] {
+            let properties = BodyProperties::from_mass_radius(mass, radius, gravitational_constant);
+            for inputs in BodyInputs::ALL {
+                let (first, second) = properties.inputs(inputs);
+                let restored = BodyProperties::from_inputs(inputs, first, second, gravitational_constant);
+                assert!((restored.mass - mass).abs() <= 1e-12 * mass, "{}: {restored:?}", inputs.name());
+                assert!((restored.radius - radius).abs() <= 1e-12 * radius, "{}: {restored:?}", inputs.name());
+            }
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use bevy::{math::DVec3, reflect::Reflect};
+use serde::{Deserialize, Serialize};
+
+// ############# Collision Mode #############
+// what happens when two bodies touch
+#[derive(Reflect, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CollisionMode {
+    // bodies pass through each other
+    Ignore,
+    // bodies stick together and become one body (mass and momentum are conserved)
+    #[default]
+    Merge,
+    // bodies bounce off each other
+    Bounce,
+}
+
+impl CollisionMode {
+    pub const ALL: [CollisionMode; 3] = [CollisionMode::Ignore, CollisionMode::Merge, CollisionMode::Bounce];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CollisionMode::Ignore => "Ignore",
+            CollisionMode::Merge => "Merge",
+            CollisionMode::Bounce => "Bounce",
+        }
+    }
+}
+
+// ############# Detection #############
+// returns all pairs (i, j) with i < j of bodies whose circles (or spheres) overlap
+pub fn find_collisions(positions: &[DVec3], radii: &[f64]) -> Vec<(usize, usize)> {
+    // sort the bodies by their left edge, then only bodies whose x ranges overlap need to be checked (sweep and prune)
+    let mut order = (0..positions.len()).collect::<Vec<_>>();
+    order.sort_by(|a, b| (positions[*a].x - radii[*a]).total_cmp(&(positions[*b].x - radii[*b])));
+
+    let mut collisions = Vec::new();
+    for (k, i) in order.iter().enumerate() {
+        let right_edge = positions[*i].x + radii[*i];
+        for j in order[k + 1..].iter() {
+            if positions[*j].x - radii[*j] > right_edge {
+                break;
+            }
+            let min_dst = radii[*i] + radii[*j];
+            if positions[*i].distance_squared(positions[*j]) < min_dst * min_dst {
+                collisions.push((*i.min(j), *i.max(j)));
+            }
+        }
+    }
+    // same order as a plain loop over all pairs would give, so the result doesnt depend on the positions
+    collisions.sort_unstable();
+    collisions
+}
+
+// groups bodies that collide with each other, also indirectly (if a touches b and b touches c, all three are one group)
+// only groups with more than one body get returned
+pub fn collision_groups(count: usize, collisions: &[(usize, usize)]) -> Vec<Vec<usize>> {
+    // union find: every body points to another body of its group, the root points to itself
+    let mut parent = (0..count).collect::<Vec<_>>();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    for (i, j) in collisions.iter() {
+        let (root_i, root_j) = (root(&mut parent, *i), root(&mut parent, *j));
+        parent[root_i.max(root_j)] = root_i.min(root_j);
+    }
+
+    let mut groups = vec![Vec::new(); count];
+    for i in 0..count {
+        let group = root(&mut parent, i);
+        groups[group].push(i);
+    }
+    groups.into_iter().filter(|group| group.len() > 1).collect()
+}
+
+// ############# Merging #############
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MergedBody {
+    pub position: DVec3,
+    pub velocity: DVec3,
+    pub mass: f64,
+    pub radius: f64,
+}
+
+// combines bodies (position, velocity, mass, radius) into one: the new body sits at the center of mass,
+// has the same total momentum and the same total volume (so the density stays the same for bodies of equal density)
+pub fn merge_bodies(bodies: impl IntoIterator<Item = (DVec3, DVec3, f64, f64)>) -> MergedBody {
+    let mut mass = 0.0;
+    let mut weighted_position = DVec3::ZERO;
+    let mut momentum = DVec3::ZERO;
+    let mut volume = 0.0;
+    for (position, velocity, body_mass, radius) in bodies {
+        mass += body_mass;
+        weighted_position += position * body_mass;
+        momentum += velocity * body_mass;
+        volume += radius.powi(3);
+    }
+    MergedBody {
+        position: if mass > 0.0 { weighted_position / mass } else { weighted_position },
+        velocity: if mass > 0.0 { momentum / mass } else { momentum },
+        mass,
+        radius: volume.cbrt(),
+    }
+}
+
+// ############# Bouncing #############
+// lets all colliding pairs bounce off each other with an impulse along the line between their centers
+// restitution: 1 is perfectly elastic, 0 means the bodies dont separate (but still stop moving into each other)
+// the overlapping bodies also get pushed apart, the heavier one moves less so the center of mass stays where it is
+// pinned bodies act as if they were infinitely heavy (they neither move nor change their velocity)
+// [Note: both the impulse and the push are applied equally and opposite to both bodies, so the momentum is conserved exactly (without pinned bodies)]
+pub fn bounce(positions: &mut [DVec3], velocities: &mut [DVec3], masses: &[f64], pinned: &[bool], radii: &[f64], collisions: &[(usize, usize)], restitution: f64) {
+    // working with the inverse masses, a pinned body simply has an inverse mass of 0
+    let inverse_mass = |i: usize| if pinned[i] || masses[i] <= 0.0 { 0.0 } else { 1.0 / masses[i] };
+    for (i, j) in collisions.iter().copied() {
+        if masses[i] <= 0.0 || masses[j] <= 0.0 {
+            continue;
+        }
+        let (inverse_mass_i, inverse_mass_j) = (inverse_mass(i), inverse_mass(j));
+        let total_inverse_mass = inverse_mass_i + inverse_mass_j;
+        if total_inverse_mass <= 0.0 {
+            continue;
+        }
+        let delta = positions[j] - positions[i];
+        let distance = delta.length();
+        // bodies at exactly the same position get pushed apart along x
+        let normal = if distance > 0.0 { delta / distance } else { DVec3::X };
+
+        // push the bodies apart until they just touch
+        let overlap = radii[i] + radii[j] - distance;
+        if overlap > 0.0 {
+            positions[i] -= normal * overlap * inverse_mass_i / total_inverse_mass;
+            positions[j] += normal * overlap * inverse_mass_j / total_inverse_mass;
+        }
+
+        // only bodies that are moving towards each other get an impulse
+        let approach_speed = (velocities[i] - velocities[j]).dot(normal);
+        if approach_speed <= 0.0 {
+            continue;
+        }
+        let impulse = (1.0 + restitution) * approach_speed / total_inverse_mass;
+        velocities[i] -= normal * impulse * inverse_mass_i;
+        velocities[j] += normal * impulse * inverse_mass_j;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn momentum(velocities: &[DVec3], masses: &[f64]) -> DVec3 {
+        velocities.iter().zip(masses.iter()).map(|(velocity, mass)| *velocity * *mass).sum()
+    }
+
+    fn kinetic_energy(velocities: &[DVec3], masses: &[f64]) -> f64 {
+        velocities.iter().zip(masses.iter()).map(|(velocity, mass)| 0.5 * mass * velocity.length_squared()).sum()
+    }
+
+    #[test]
+    fn bounce_conserves_momentum() {
+        let masses = [3.0e15, 1.0e14, 5.0e14];
+        let radii = [100.0, 20.0, 40.0];
+        for restitution in [0.0, 0.3, 0.8, 1.0] {
+            let mut positions = [DVec3::new(0.0, 0.0, 0.0), DVec3::new(110.0, 15.0, 0.0), DVec3::new(-30.0, 120.0, 0.0)];
+            let mut velocities = [DVec3::new(1.0, 0.5, 0.0), DVec3::new(-12.0, 2.0, 0.0), DVec3::new(4.0, -9.0, 0.0)];
+            let momentum_before = momentum(&velocities, &masses);
+            let center_of_mass_before = positions.iter().zip(masses.iter()).map(|(position, mass)| *position * *mass).sum::<DVec3>();
+
+            let collisions = find_collisions(&positions, &radii);
+            assert_eq!(collisions, vec![(0, 1), (0, 2)]);
+            bounce(&mut positions, &mut velocities, &masses, &[false; 3], &radii, &collisions, restitution);
+
+            let momentum_after = momentum(&velocities, &masses);
+            let center_of_mass_after = positions.iter().zip(masses.iter()).map(|(position, mass)| *position * *mass).sum::<DVec3>();
+            assert!((momentum_after - momentum_before).length() <= 1e-12 * momentum_before.length(), "restitution {restitution}");
+            assert!((center_of_mass_after - center_of_mass_before).length() <= 1e-12 * center_of_mass_before.length(), "restitution {restitution}");
+        }
+    }
+
+    #[test]
+    fn merge_conserves_mass_and_momentum() {
+        let bodies = [
+            (DVec3::new(0.0, 0.0, 0.0), DVec3::new(1.0, 0.5, 0.0), 3.0e15, 100.0),
+            (DVec3::new(110.0, 15.0, -5.0), DVec3::new(-12.0, 2.0, 1.0), 1.0e14, 20.0),
+            (DVec3::new(-30.0, 120.0, 8.0), DVec3::new(4.0, -9.0, 0.0), 5.0e14, 40.0),
+        ];
+        let mass = 3.0e15 + 1.0e14 + 5.0e14;
+        let momentum = bodies.iter().map(|(_, velocity, mass, _)| *velocity * *mass).sum::<DVec3>();
+        let center_of_mass = bodies.iter().map(|(position, _, mass, _)| *position * *mass).sum::<DVec3>() / mass;
+
+        let merged = merge_bodies(bodies);
+
+        assert!((merged.mass - mass).abs() <= 1e-12 * mass);
+        assert!((merged.velocity * merged.mass - momentum).length() <= 1e-12 * momentum.length());
+        assert!((merged.position - center_of_mass).length() <= 1e-12 * center_of_mass.length());
+        // same volume as all bodies together
+        let radius = (100.0_f64.powi(3) + 20.0_f64.powi(3) + 40.0_f64.powi(3)).cbrt();
+        assert!((merged.radius - radius).abs() <= 1e-12 * radius);
+    }
+
+    #[test]
+    fn collision_groups_are_transitive() {
+        // 1 touches 3 and 3 touches 4, but 1 and 4 dont touch each other; 0 and 2 and 5 dont touch anything
+        let positions = [
+            DVec3::new(-100.0, 0.0, 0.0),
+            DVec3::new(0.0, 0.0, 0.0),
+            DVec3::new(0.0, 100.0, 0.0),
+            DVec3::new(15.0, 0.0, 0.0),
+            DVec3::new(30.0, 0.0, 0.0),
+            DVec3::new(0.0, -100.0, 0.0),
+        ];
+        let radii = [10.0; 6];
+        let collisions = find_collisions(&positions, &radii);
+        assert_eq!(collisions, vec![(1, 3), (3, 4)]);
+        assert_eq!(collision_groups(positions.len(), &collisions), vec![vec![1, 3, 4]]);
+
+        // two separate groups
+        assert_eq!(collision_groups(6, &[(4, 5), (0, 2), (2, 3)]), vec![vec![0, 2, 3], vec![4, 5]]);
+    }
+
+    #[test]
+    fn elastic_bounce_conserves_energy() {
+        let masses = [2.0, 1.0];
+        let radii = [1.0, 1.0];
+        let mut positions = [DVec3::new(0.0, 0.0, 0.0), DVec3::new(1.5, 0.5, 0.0)];
+        let mut velocities = [DVec3::new(1.0, 0.0, 0.0), DVec3::new(-1.0, 0.0, 0.0)];
+        let energy_before = kinetic_energy(&velocities, &masses);
+
+        bounce(&mut positions, &mut velocities, &masses, &[false; 2], &radii, &[(0, 1)], 1.0);
+
+        assert!((kinetic_energy(&velocities, &masses) - energy_before).abs() < 1e-12);
+        // the bodies are separated and move apart
+        assert!((positions[1] - positions[0]).length() >= 2.0 - 1e-12);
+        assert!((velocities[1] - velocities[0]).dot(positions[1] - positions[0]) > 0.0);
+    }
+
+    #[test]
+    fn pinned_body_does_not_move() {
+        let masses = [1.0, 1.0e6];
+        let radii = [1.0, 1.0];
+        let mut positions = [DVec3::new(0.0, 0.0, 0.0), DVec3::new(1.5, 0.0, 0.0)];
+        let mut velocities = [DVec3::new(0.0, 0.0, 0.0), DVec3::new(-1.0, 0.0, 0.0)];
+
+        // the light body is pinned, so the heavy one bounces off it as if it were a wall
+        bounce(&mut positions, &mut velocities, &masses, &[true, false], &radii, &[(0, 1)], 1.0);
+
+        assert_eq!(positions[0], DVec3::ZERO);
+        assert_eq!(velocities[0], DVec3::ZERO);
+        assert!((positions[1] - DVec3::new(2.0, 0.0, 0.0)).length() < 1e-12);
+        assert!((velocities[1] - DVec3::new(1.0, 0.0, 0.0)).length() < 1e-12);
+    }
+}
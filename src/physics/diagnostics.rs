@@ -0,0 +1,61 @@
+use bevy::math::DVec3;
+
+use super::NBodySystem;
+
+// ############# Conserved Quantities #############
+// quantities that stay constant in an exact simulation (as long as no bodies get merged or added),
+// their change over time shows how trustworthy a run is
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Conservation {
+    pub kinetic_energy: f64,
+    pub potential_energy: f64,
+    pub momentum: DVec3,
+    // around the origin
+    pub angular_momentum: DVec3,
+    // sum of the absolute values of the momenta / angular momenta of all bodies
+    // [Note: the total momentum is often zero, so its drift gets measured relative to these instead]
+    pub momentum_scale: f64,
+    pub angular_momentum_scale: f64,
+}
+
+impl Conservation {
+    pub fn total_energy(&self) -> f64 {
+        self.kinetic_energy + self.potential_energy
+    }
+
+    // relative changes compared to an earlier state: (energy, momentum, angular momentum)
+    pub fn drift(&self, initial: &Conservation) -> (f64, f64, f64) {
+        (
+            relative((self.total_energy() - initial.total_energy()).abs(), initial.total_energy().abs()),
+            relative((self.momentum - initial.momentum).length(), initial.momentum_scale),
+            relative((self.angular_momentum - initial.angular_momentum).length(), initial.angular_momentum_scale),
+        )
+    }
+}
+
+fn relative(change: f64, scale: f64) -> f64 {
+    if scale > 0.0 { change / scale } else { change }
+}
+
+impl NBodySystem {
+    // calculates the conserved quantities of the current state
+    // [Note: the potential energy comes from the force law (with the same softening as the forces), otherwise it wouldnt be conserved]
+    pub fn conservation(&self) -> Conservation {
+        let mut conservation = Conservation::default();
+        let constants = self.force_constants();
+        for i in 0..self.len() {
+            let (position, velocity, mass) = (self.positions[i], self.velocities[i], self.masses[i]);
+            conservation.kinetic_energy += 0.5 * mass * velocity.length_squared();
+            conservation.momentum += velocity * mass;
+            conservation.angular_momentum += position.cross(velocity) * mass;
+            conservation.momentum_scale += (velocity * mass).length();
+            conservation.angular_momentum_scale += (position.cross(velocity) * mass).length();
+
+            for j in (i + 1)..self.len() {
+                let distance = (self.positions[j] - position).length();
+                conservation.potential_energy += self.force_law.potential_energy(distance, self.force_body(i), self.force_body(j), &constants);
+            }
+        }
+        conservation
+    }
+}
@@ -0,0 +1,297 @@
+use std::fmt::Debug;
+
+use bevy::math::DVec3;
+
+use super::{gravitational_acceleration, Dimension};
+
+// ############# Force Law #############
+// how two bodies act on each other; the NBodySystem only sums up what the active force law returns for every pair
+// (or for every group of bodies with barnes-hut, then the source is the whole group at its center of mass)
+// own force laws just need to implement this trait (and Clone), and can then be registered in the ForceLaws resource
+pub trait ForceLaw: ForceLawClone + Debug + Send + Sync {
+    fn name(&self) -> &str;
+
+    // acceleration of body caused by source, delta is the position of the source - own position
+    fn acceleration(&self, delta: DVec3, body: ForceBody, source: ForceBody, constants: &ForceConstants) -> DVec3;
+
+    // acceleration of body (at position) caused by all the sources (position, properties)
+    // [Note: the NBodySystem calls this once per body, so only one dynamic call is needed for all sources and the loop can get inlined;
+    // there is no need to implement it yourself]
+    fn total_acceleration(&self, position: DVec3, body: ForceBody, sources: &[(DVec3, ForceBody)], constants: &ForceConstants) -> DVec3 {
+        sources.iter().map(|(source_position, source)| self.acceleration(*source_position - position, body, *source, constants)).sum()
+    }
+
+    // potential energy of a pair of bodies at the given distance (for the energy diagnostics, so it has to match the acceleration)
+    fn potential_energy(&self, distance: f64, body: ForceBody, source: ForceBody, constants: &ForceConstants) -> f64;
+
+    // values of the force law that can be changed in the UI (name, value in simulation units, dimension to show it in the display units)
+    fn parameters(&self) -> Vec<(&'static str, f64, Dimension)> {
+        Vec::new()
+    }
+
+    // changes the parameter with the given name (see parameters), unknown names get ignored
+    fn set_parameter(&mut self, _name: &str, _value: f64) {}
+
+    // true if the bodies need a charge (only then it gets shown in the UI)
+    fn uses_charge(&self) -> bool {
+        false
+    }
+}
+
+// lets a Box<dyn ForceLaw> be cloned (every NBodySystem gets its own copy of the active force law)
+// [Note: this is implemented automatically for every force law that derives Clone]
+pub trait ForceLawClone {
+    fn clone_box(&self) -> Box<dyn ForceLaw>;
+}
+
+impl<T: ForceLaw + Clone + 'static> ForceLawClone for T {
+    fn clone_box(&self) -> Box<dyn ForceLaw> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn ForceLaw> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+// what a force law knows about a body (or a group of bodies)
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ForceBody {
+    pub mass: f64,
+    pub charge: f64,
+}
+
+// settings of the simulation that every force law gets
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ForceConstants {
+    // G in the units of the simulation
+    pub gravitational_constant: f64,
+    // plummer softening length, every force law should use softened_distance instead of the real distance
+    pub softening: f64,
+}
+
+// distance with plummer softening: sqrt(r² + softening²)
+pub fn softened_distance(distance: f64, softening: f64) -> f64 {
+    (distance * distance + softening * softening).sqrt()
+}
+
+// ############# Newtonian Gravity #############
+// F = G * m1 * m2 / r²
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Newtonian;
+
+impl ForceLaw for Newtonian {
+    fn name(&self) -> &str {
+        "Newtonian Gravity"
+    }
+
+    fn acceleration(&self, delta: DVec3, _body: ForceBody, source: ForceBody, constants: &ForceConstants) -> DVec3 {
+        gravitational_acceleration(delta, source.mass, constants.gravitational_constant, constants.softening)
+    }
+
+    fn potential_energy(&self, distance: f64, body: ForceBody, source: ForceBody, constants: &ForceConstants) -> f64 {
+        let distance = softened_distance(distance, constants.softening);
+        if distance > 0.0 { -constants.gravitational_constant * body.mass * source.mass / distance } else { 0.0 }
+    }
+}
+
+// ############# Power Law #############
+// F = G * m1 * m2 / r^exponent (2 is newtonian gravity, with 1 every circular orbit has the same speed)
+// [Note: only for the exponent 2 the orbits are closed ellipses, otherwise they turn around like rosettes]
+#[derive(Clone, Copy, Debug)]
+pub struct PowerLaw {
+    pub exponent: f64,
+}
+
+impl Default for PowerLaw {
+    fn default() -> Self {
+        Self { exponent: 1.0 }
+    }
+}
+
+impl ForceLaw for PowerLaw {
+    fn name(&self) -> &str {
+        "Power Law"
+    }
+
+    fn acceleration(&self, delta: DVec3, _body: ForceBody, source: ForceBody, constants: &ForceConstants) -> DVec3 {
+        let distance = softened_distance(delta.length(), constants.softening);
+        if distance > 0.0 {
+            delta * constants.gravitational_constant * source.mass / distance.powf(self.exponent + 1.0)
+        } else {
+            DVec3::ZERO
+        }
+    }
+
+    fn potential_energy(&self, distance: f64, body: ForceBody, source: ForceBody, constants: &ForceConstants) -> f64 {
+        let distance = softened_distance(distance, constants.softening);
+        if distance <= 0.0 {
+            return 0.0;
+        }
+        let factor = constants.gravitational_constant * body.mass * source.mass;
+        // the integral of 1/r is the logarithm, for every other exponent it is a power again
+        if (self.exponent - 1.0).abs() < 1e-9 {
+            factor * distance.ln()
+        } else {
+            -factor / ((self.exponent - 1.0) * distance.powf(self.exponent - 1.0))
+        }
+    }
+
+    fn parameters(&self) -> Vec<(&'static str, f64, Dimension)> {
+        vec![("Exponent", self.exponent, Dimension::NONE)]
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f64) {
+        if name == "Exponent" {
+            self.exponent = value;
+        }
+    }
+}
+
+// ############# Yukawa Potential #############
+// V = -G * m1 * m2 * e^(-r / range) / r: newtonian gravity close by, but it fades away (exponentially) further away than range
+#[derive(Clone, Copy, Debug)]
+pub struct Yukawa {
+    // in simulation units (length)
+    pub range: f64,
+}
+
+impl Default for Yukawa {
+    fn default() -> Self {
+        Self { range: 1000.0 }
+    }
+}
+
+impl ForceLaw for Yukawa {
+    fn name(&self) -> &str {
+        "Yukawa"
+    }
+
+    fn acceleration(&self, delta: DVec3, _body: ForceBody, source: ForceBody, constants: &ForceConstants) -> DVec3 {
+        let distance = softened_distance(delta.length(), constants.softening);
+        if distance <= 0.0 || self.range <= 0.0 {
+            return DVec3::ZERO;
+        }
+        // -dV/dr (divided by the own mass) = G * m * e^(-r / range) * (1 / r² + 1 / (range * r)), the extra 1 / r normalizes delta
+        let force = constants.gravitational_constant * source.mass * (-distance / self.range).exp() * (1.0 / distance.powi(2) + 1.0 / (self.range * distance));
+        delta * force / distance
+    }
+
+    fn potential_energy(&self, distance: f64, body: ForceBody, source: ForceBody, constants: &ForceConstants) -> f64 {
+        let distance = softened_distance(distance, constants.softening);
+        if distance <= 0.0 || self.range <= 0.0 {
+            return 0.0;
+        }
+        -constants.gravitational_constant * body.mass * source.mass * (-distance / self.range).exp() / distance
+    }
+
+    fn parameters(&self) -> Vec<(&'static str, f64, Dimension)> {
+        vec![("Range", self.range, Dimension::LENGTH)]
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f64) {
+        if name == "Range" {
+            self.range = value;
+        }
+    }
+}
+
+// ############# Charged Bodies #############
+// newtonian gravity plus a coulomb force between the charges: F = k * q1 * q2 / r² (equal charges push each other away)
+#[derive(Clone, Copy, Debug)]
+pub struct Charged {
+    // k in simulation units, the default is the real value for SI units (and charges in coulomb)
+    pub coulomb_constant: f64,
+}
+
+impl Default for Charged {
+    fn default() -> Self {
+        Self { coulomb_constant: 8.987_551_792e9 }
+    }
+}
+
+impl ForceLaw for Charged {
+    fn name(&self) -> &str {
+        "Gravity + Charges"
+    }
+
+    fn acceleration(&self, delta: DVec3, body: ForceBody, source: ForceBody, constants: &ForceConstants) -> DVec3 {
+        let gravity = gravitational_acceleration(delta, source.mass, constants.gravitational_constant, constants.softening);
+        // massless bodies can not be accelerated by a force, they only feel the gravity
+        if body.mass <= 0.0 || body.charge == 0.0 {
+            return gravity;
+        }
+        // same shape as gravity, but with k * q1 * q2 / m1 instead of G * m2, and the opposite sign
+        gravity - gravitational_acceleration(delta, body.charge * source.charge / body.mass, self.coulomb_constant, constants.softening)
+    }
+
+    fn potential_energy(&self, distance: f64, body: ForceBody, source: ForceBody, constants: &ForceConstants) -> f64 {
+        let distance = softened_distance(distance, constants.softening);
+        if distance > 0.0 {
+            (self.coulomb_constant * body.charge * source.charge - constants.gravitational_constant * body.mass * source.mass) / distance
+        } else {
+            0.0
+        }
+    }
+
+    fn parameters(&self) -> Vec<(&'static str, f64, Dimension)> {
+        vec![("Coulomb Constant", self.coulomb_constant, Dimension::COULOMB_CONSTANT)]
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f64) {
+        if name == "Coulomb Constant" {
+            self.coulomb_constant = value;
+        }
+    }
+
+    fn uses_charge(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_parameter_changes_parameters() {
+        let mut force_laws: Vec<Box<dyn ForceLaw>> = vec![Box::new(PowerLaw::default()), Box::new(Yukawa::default()), Box::new(Charged::default())];
+        for force_law in force_laws.iter_mut() {
+            let before = force_law.parameters().into_iter().map(|(name, value, _)| (name, value)).collect::<Vec<_>>();
+            for (name, value) in before.iter() {
+                force_law.set_parameter(name, value * 2.0 + 1.0);
+            }
+            force_law.set_parameter("Unknown", 5.0);
+            let after = force_law.parameters().into_iter().map(|(name, value, _)| (name, value)).collect::<Vec<_>>();
+            assert_eq!(after, before.iter().map(|(name, value)| (*name, value * 2.0 + 1.0)).collect::<Vec<_>>(), "{}", force_law.name());
+        }
+    }
+
+    // the force on the body is -grad of the potential energy, with the source at delta: dV/dr * delta / r
+    #[test]
+    fn acceleration_is_gradient_of_potential() {
+        let force_laws: Vec<Box<dyn ForceLaw>> = vec![
+            Box::new(Newtonian),
+            Box::new(PowerLaw::default()),
+            Box::new(PowerLaw { exponent: 3.0 }),
+            Box::new(Yukawa { range: 2.0 }),
+            Box::new(Charged::default()),
+        ];
+        let constants = ForceConstants { gravitational_constant: 1.0, softening: 0.1 };
+        let body = ForceBody { mass: 2.0, charge: 1e-5 };
+        let source = ForceBody { mass: 3.0, charge: -2e-5 };
+        for force_law in force_laws {
+            for delta in [DVec3::new(0.3, -0.4, 0.0), DVec3::new(1.0, 0.0, 0.0), DVec3::new(-1.0, 2.0, 2.0)] {
+                let distance = delta.length();
+                let step = distance * 1e-5;
+                let derivative = (force_law.potential_energy(distance + step, body, source, &constants)
+                    - force_law.potential_energy(distance - step, body, source, &constants)) / (2.0 * step);
+                let expected = delta / distance * derivative / body.mass;
+                let acceleration = force_law.acceleration(delta, body, source, &constants);
+                assert!((acceleration - expected).length() < 1e-6 * expected.length(), "{}: {acceleration} != {expected}", force_law.name());
+            }
+        }
+    }
+}
@@ -0,0 +1,320 @@
+use bevy::{math::DVec3, reflect::Reflect};
+use serde::{Deserialize, Serialize};
+
+use super::NBodySystem;
+
+// ############# Integrator #############
+// numerical method used to advance the system by one step
+#[derive(Reflect, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Integrator {
+    // first order, but symplectic: velocity first, then position with the new velocity
+    #[default]
+    SymplecticEuler,
+    // second order, kick-drift-kick
+    VelocityVerlet,
+    // second order, drift-kick-drift
+    Leapfrog,
+    // classic 4th order Runge-Kutta (not symplectic, so the energy slowly drifts away)
+    RungeKutta4,
+    // 4th order symplectic integrator (three velocity verlet steps with special weights)
+    Yoshida4,
+    // embedded runge-kutta 5(4) with adaptive step size: every step gets split into as many sub steps as needed to stay below the tolerance
+    DormandPrince45,
+}
+
+impl Integrator {
+    pub const ALL: [Integrator; 6] = [
+        Integrator::SymplecticEuler,
+        Integrator::VelocityVerlet,
+        Integrator::Leapfrog,
+        Integrator::RungeKutta4,
+        Integrator::Yoshida4,
+        Integrator::DormandPrince45,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Integrator::SymplecticEuler => "Symplectic Euler",
+            Integrator::VelocityVerlet => "Velocity Verlet",
+            Integrator::Leapfrog => "Leapfrog",
+            Integrator::RungeKutta4 => "Runge-Kutta 4",
+            Integrator::Yoshida4 => "Yoshida 4",
+            Integrator::DormandPrince45 => "Dormand-Prince 5(4)",
+        }
+    }
+
+    pub fn is_adaptive(&self) -> bool {
+        *self == Integrator::DormandPrince45
+    }
+}
+
+// coefficients of the 4th order yoshida integrator
+// w1 = 1 / (2 - 2^(1/3)), w0 = -2^(1/3) / (2 - 2^(1/3))
+const YOSHIDA_W1: f64 = 1.351_207_191_959_657_6;
+const YOSHIDA_W0: f64 = -1.702_414_383_919_315_3;
+const YOSHIDA_C: [f64; 4] = [YOSHIDA_W1 / 2.0, (YOSHIDA_W0 + YOSHIDA_W1) / 2.0, (YOSHIDA_W0 + YOSHIDA_W1) / 2.0, YOSHIDA_W1 / 2.0];
+const YOSHIDA_D: [f64; 3] = [YOSHIDA_W1, YOSHIDA_W0, YOSHIDA_W1];
+
+// butcher tableau of dormand-prince 5(4); the last row is also the 5th order solution (first same as last)
+const DOPRI_A: [[f64; 6]; 6] = [
+    [1.0 / 5.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [3.0 / 40.0, 9.0 / 40.0, 0.0, 0.0, 0.0, 0.0],
+    [44.0 / 45.0, -56.0 / 15.0, 32.0 / 9.0, 0.0, 0.0, 0.0],
+    [19372.0 / 6561.0, -25360.0 / 2187.0, 64448.0 / 6561.0, -212.0 / 729.0, 0.0, 0.0],
+    [9017.0 / 3168.0, -355.0 / 33.0, 46732.0 / 5247.0, 49.0 / 176.0, -5103.0 / 18656.0, 0.0],
+    [35.0 / 384.0, 0.0, 500.0 / 1113.0, 125.0 / 192.0, -2187.0 / 6784.0, 11.0 / 84.0],
+];
+// difference between the 5th and the 4th order weights, used to estimate the error of a step
+const DOPRI_E: [f64; 7] = [
+    35.0 / 384.0 - 5179.0 / 57600.0,
+    0.0,
+    500.0 / 1113.0 - 7571.0 / 16695.0,
+    125.0 / 192.0 - 393.0 / 640.0,
+    -2187.0 / 6784.0 + 92097.0 / 339200.0,
+    11.0 / 84.0 - 187.0 / 2100.0,
+    -1.0 / 40.0,
+];
+// sub steps never get smaller than this fraction of the whole step, otherwise a close encounter could stall the simulation
+const DOPRI_MIN_STEP_FRACTION: f64 = 1e-4;
+
+// ############# Steps #############
+impl NBodySystem {
+    pub(super) fn step_symplectic_euler(&mut self, dt: f64) {
+        self.accelerations = self.accelerations_at(&self.positions, &self.velocities);
+        self.kick(dt);
+        self.drift(dt);
+    }
+
+    pub(super) fn step_velocity_verlet(&mut self, dt: f64) {
+        self.accelerations = self.accelerations_at(&self.positions, &self.velocities);
+        self.kick(dt / 2.0);
+        self.drift(dt);
+        self.accelerations = self.accelerations_at(&self.positions, &self.velocities);
+        self.kick(dt / 2.0);
+    }
+
+    pub(super) fn step_leapfrog(&mut self, dt: f64) {
+        self.drift(dt / 2.0);
+        self.accelerations = self.accelerations_at(&self.positions, &self.velocities);
+        self.kick(dt);
+        self.drift(dt / 2.0);
+    }
+
+    pub(super) fn step_runge_kutta4(&mut self, dt: f64) {
+        // every k is a pair of (derivative of the position, derivative of the velocity) = (velocity, acceleration)
+        let k1_x = self.velocities.clone();
+        let k1_v = self.accelerations_at(&self.positions, &self.velocities);
+
+        let k2_x = offset(&self.velocities, &k1_v, dt / 2.0);
+        let k2_v = self.accelerations_at(&offset(&self.positions, &k1_x, dt / 2.0), &k2_x);
+
+        let k3_x = offset(&self.velocities, &k2_v, dt / 2.0);
+        let k3_v = self.accelerations_at(&offset(&self.positions, &k2_x, dt / 2.0), &k3_x);
+
+        let k4_x = offset(&self.velocities, &k3_v, dt);
+        let k4_v = self.accelerations_at(&offset(&self.positions, &k3_x, dt), &k4_x);
+
+        for i in 0..self.len() {
+            self.positions[i] += (k1_x[i] + 2.0 * k2_x[i] + 2.0 * k3_x[i] + k4_x[i]) * dt / 6.0;
+            self.velocities[i] += (k1_v[i] + 2.0 * k2_v[i] + 2.0 * k3_v[i] + k4_v[i]) * dt / 6.0;
+        }
+        self.accelerations = k1_v;
+    }
+
+    pub(super) fn step_yoshida4(&mut self, dt: f64) {
+        for (c, d) in YOSHIDA_C.iter().zip(YOSHIDA_D.iter()) {
+            self.drift(c * dt);
+            self.accelerations = self.accelerations_at(&self.positions, &self.velocities);
+            self.kick(d * dt);
+        }
+        self.drift(YOSHIDA_C[3] * dt);
+    }
+
+    pub(super) fn step_dormand_prince45(&mut self, dt: f64) {
+        // continue with the step size of the last call, this is usually a good guess
+        let mut step_size = if self.adaptive_step > 0.0 { self.adaptive_step.min(dt) } else { dt };
+        let min_step_size = dt * DOPRI_MIN_STEP_FRACTION;
+        let mut remaining = dt;
+        self.rejected_steps = 0;
+        let mut k1_v = self.accelerations_at(&self.positions, &self.velocities);
+
+        while remaining > 0.0 {
+            // the last sub step gets cut off, so exactly dt is covered
+            let last = step_size >= remaining;
+            let h = if last { remaining } else { step_size };
+
+            let (positions, velocities, accelerations, error) = self.dormand_prince45_trial(h, &k1_v);
+
+            let accepted = error <= 1.0 || h <= min_step_size;
+            if accepted {
+                self.positions = positions;
+                self.velocities = velocities;
+                // first same as last: the acceleration at the end of this sub step is the start of the next one
+                k1_v = accelerations;
+                remaining = if last { 0.0 } else { remaining - h };
+            } else {
+                self.rejected_steps += 1;
+            }
+
+            // new step size based on the estimated error (with some safety margin and limits so it doesnt jump around too much)
+            let factor = if error > 0.0 { (0.9 * error.powf(-0.2)).clamp(0.2, 5.0) } else { 5.0 };
+            // a sub step that was only cut off to hit dt exactly should not shrink the step size for the next call
+            if !(accepted && last) || h * factor > step_size {
+                step_size = (h * factor).max(min_step_size);
+            }
+        }
+
+        self.adaptive_step = step_size.min(dt);
+        self.accelerations = k1_v;
+    }
+
+    // one dormand-prince step of size h, returns the new positions, velocities, accelerations and the error relative to the tolerance
+    fn dormand_prince45_trial(&self, h: f64, k1_v: &[DVec3]) -> (Vec<DVec3>, Vec<DVec3>, Vec<DVec3>, f64) {
+        let mut k_x = vec![self.velocities.clone()];
+        let mut k_v = vec![k1_v.to_vec()];
+
+        for a in DOPRI_A.iter() {
+            let (positions, velocities) = self.dormand_prince45_stage(h, a, &k_x, &k_v);
+            k_v.push(self.accelerations_at(&positions, &velocities));
+            k_x.push(velocities);
+        }
+        // the last stage is the 5th order solution (its velocity is already in k_x, but the positions are needed too)
+        let (positions, velocities) = self.dormand_prince45_stage(h, &DOPRI_A[5], &k_x, &k_v);
+
+        // largest error of all bodies, measured relative to the tolerance
+        let mut error: f64 = 0.0;
+        for i in 0..self.len() {
+            let mut error_x = DVec3::ZERO;
+            let mut error_v = DVec3::ZERO;
+            for (j, weight) in DOPRI_E.iter().enumerate() {
+                error_x += k_x[j][i] * *weight * h;
+                error_v += k_v[j][i] * *weight * h;
+            }
+            let scale_x = self.tolerance * (1.0 + self.positions[i].length().max(positions[i].length()));
+            let scale_v = self.tolerance * (1.0 + self.velocities[i].length().max(velocities[i].length()));
+            error = error.max(error_x.length() / scale_x).max(error_v.length() / scale_v);
+        }
+
+        let accelerations = k_v.pop().unwrap_or_default();
+        (positions, velocities, accelerations, error)
+    }
+
+    // state (positions, velocities) of one intermediate stage: current state + h * weighted sum of the previous stages
+    fn dormand_prince45_stage(&self, h: f64, a: &[f64; 6], k_x: &[Vec<DVec3>], k_v: &[Vec<DVec3>]) -> (Vec<DVec3>, Vec<DVec3>) {
+        let mut positions = self.positions.clone();
+        let mut velocities = self.velocities.clone();
+        for ((weight, stage_x), stage_v) in a.iter().zip(k_x.iter()).zip(k_v.iter()) {
+            for i in 0..self.len() {
+                positions[i] += stage_x[i] * *weight * h;
+                velocities[i] += stage_v[i] * *weight * h;
+            }
+        }
+        (positions, velocities)
+    }
+
+    // updates the velocities with the current accelerations
+    fn kick(&mut self, dt: f64) {
+        for (velocity, acceleration) in self.velocities.iter_mut().zip(self.accelerations.iter()) {
+            *velocity += *acceleration * dt;
+        }
+    }
+
+    // updates the positions with the current velocities
+    fn drift(&mut self, dt: f64) {
+        for (position, velocity) in self.positions.iter_mut().zip(self.velocities.iter()) {
+            *position += *velocity * dt;
+        }
+    }
+}
+
+// returns values + derivatives * dt (used for the intermediate states of runge-kutta)
+fn offset(values: &[DVec3], derivatives: &[DVec3], dt: f64) -> Vec<DVec3> {
+    values.iter().zip(derivatives.iter()).map(|(value, derivative)| *value + *derivative * dt).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::TAU;
+
+    use super::*;
+
+    // a light body on an orbit with a = 1 around a heavy one, starting at the periapsis (with G = 1)
+    fn kepler_orbit(integrator: Integrator, eccentricity: f64) -> NBodySystem {
+        let (mass, light_mass) = (1.0, 1e-3);
+        let gravitational_parameter = mass + light_mass;
+        let mut system = NBodySystem { integrator, gravitational_constant: 1.0, ..NBodySystem::default() };
+        // vis-viva at the periapsis, the velocity gets split so the center of mass stays at rest
+        let (distance, speed) = (1.0 - eccentricity, (gravitational_parameter * (1.0 + eccentricity) / (1.0 - eccentricity)).sqrt());
+        let (position, velocity) = (DVec3::new(distance, 0.0, 0.0), DVec3::new(0.0, speed, 0.0));
+        system.add_body(-position * light_mass / gravitational_parameter, -velocity * light_mass / gravitational_parameter, mass);
+        system.add_body(position * mass / gravitational_parameter, velocity * mass / gravitational_parameter, light_mass);
+        system
+    }
+
+    // the orbit gets compared after about 0.3 of a period (not a whole one, the errors of some integrators cancel out over a closed orbit)
+    const DURATION: f64 = 0.3 * TAU;
+
+    // position of the light body relative to the heavy one and the energy (at the end and at the start) after DURATION with the given number of steps
+    fn run(integrator: Integrator, steps: u32) -> (DVec3, f64, f64) {
+        let mut system = kepler_orbit(integrator, 0.5);
+        let energy = system.conservation().total_energy();
+        let dt = DURATION / steps as f64;
+        for _ in 0..steps {
+            system.step(dt);
+        }
+        (system.positions[1] - system.positions[0], system.conservation().total_energy(), energy)
+    }
+
+    #[test]
+    fn order_and_energy_error() {
+        // a much more accurate solution to compare with
+        let (reference, _, _) = run(Integrator::Yoshida4, 20000);
+        // (integrator, order, largest relative energy error with 200 steps)
+        for (integrator, order, energy_error) in [
+            (Integrator::SymplecticEuler, 1.0, 5e-3),
+            (Integrator::VelocityVerlet, 2.0, 5e-4),
+            (Integrator::Leapfrog, 2.0, 1e-4),
+            (Integrator::RungeKutta4, 4.0, 5e-8),
+            (Integrator::Yoshida4, 4.0, 2e-7),
+        ] {
+            let errors = [100, 200, 400].map(|steps| {
+                let (delta, energy, initial_energy) = run(integrator, steps);
+                ((delta - reference).length(), ((energy - initial_energy) / initial_energy).abs())
+            });
+            // with half the step size the error gets 2^order times smaller
+            for pair in errors.windows(2) {
+                let measured = (pair[0].0 / pair[1].0).log2();
+                assert!((measured - order).abs() < 0.3, "{}: order {measured} instead of {order}", integrator.name());
+            }
+            assert!(errors[1].1 < energy_error, "{}: energy error {}", integrator.name(), errors[1].1);
+        }
+    }
+
+    #[test]
+    fn tighter_tolerance_smaller_error() {
+        let (reference, _, _) = run(Integrator::Yoshida4, 20000);
+        let errors = [1e-4, 1e-6, 1e-8].map(|tolerance| {
+            let mut system = NBodySystem { tolerance, ..kepler_orbit(Integrator::DormandPrince45, 0.5) };
+            // split into a few steps like the FixedUpdate ticks, the sub steps continue where the last step stopped
+            for _ in 0..10 {
+                system.step(DURATION / 10.0);
+            }
+            (system.positions[1] - system.positions[0] - reference).length()
+        });
+        assert!(errors[1] < errors[0] / 10.0 && errors[2] < errors[1] / 10.0, "{errors:?}");
+    }
+
+    #[test]
+    fn close_encounter_rejects_steps() {
+        // a whole orbit in one step, the periapsis passage (at a distance of 0.05) needs far smaller sub steps than the rest
+        let mut system = kepler_orbit(Integrator::DormandPrince45, 0.95);
+        let energy = system.conservation().total_energy();
+        system.step(TAU / 1.001_f64.sqrt());
+        assert!(system.rejected_steps > 0);
+        // the sub steps stay small enough to get through the periapsis without a big energy error
+        assert!(((system.conservation().total_energy() - energy) / energy).abs() < 1e-3);
+        // the count is only for the last step
+        system.step(1e-6);
+        assert_eq!(system.rejected_steps, 0);
+    }
+}
@@ -0,0 +1,301 @@
+use std::f64::consts::{PI, TAU};
+
+use bevy::{math::{DQuat, DVec3}, reflect::Reflect};
+use serde::{Deserialize, Serialize};
+
+// ############# Orbit Direction #############
+// in which direction a body goes around its primary (seen from above, i.e. from +z)
+#[derive(Reflect, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OrbitDirection {
+    // counterclockwise
+    #[default]
+    Prograde,
+    // clockwise
+    Retrograde,
+}
+
+impl OrbitDirection {
+    pub const ALL: [OrbitDirection; 2] = [OrbitDirection::Prograde, OrbitDirection::Retrograde];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            OrbitDirection::Prograde => "Prograde",
+            OrbitDirection::Retrograde => "Retrograde",
+        }
+    }
+}
+
+// ############# Orbit Shape #############
+// shape of the orbit a new body gets placed on
+#[derive(Reflect, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct OrbitShape {
+    // 0 is a circle, below 1 an ellipse; the body starts at the periapsis (the closest point to the primary)
+    pub eccentricity: f64,
+    pub direction: OrbitDirection,
+    // in radians: tilts the orbit around the line between the body and the primary, 0 keeps it in the xy plane
+    pub inclination: f64,
+}
+
+// ############# Orbit Velocity #############
+// velocity a body at the given position needs to orbit a primary (position, velocity and mass, e.g. a planet or the barycenter)
+// mass: mass of the orbiting body itself (the two bodies orbit their common center of mass, so both masses count)
+// [Note: the primary keeps its velocity, for a body much lighter than its primary the orbit is still very close to the wanted one]
+pub fn orbit_velocity(
+    position: DVec3,
+    primary_position: DVec3,
+    primary_velocity: DVec3,
+    primary_mass: f64,
+    mass: f64,
+    gravitational_constant: f64,
+    shape: OrbitShape,
+) -> DVec3 {
+    let delta = position - primary_position;
+    let distance = delta.length();
+    let gravitational_parameter = gravitational_constant * (primary_mass + mass);
+    if distance <= 0.0 || gravitational_parameter <= 0.0 {
+        return primary_velocity;
+    }
+    // vis-viva at the periapsis: v² = GM (2/r - 1/a) with r = a (1 - e)
+    let speed = (gravitational_parameter * (1.0 + shape.eccentricity.max(0.0)) / distance).sqrt();
+
+    // a retrograde orbit is one with an inclination above 90°
+    let inclination = match shape.direction {
+        OrbitDirection::Prograde => shape.inclination,
+        OrbitDirection::Retrograde => PI - shape.inclination,
+    };
+    // normal of the orbital plane: +z, tilted by the inclination around the radial direction
+    let radial = delta / distance;
+    let unrotated_normal = if radial.cross(DVec3::Z).length_squared() > 1e-12 { DVec3::Z } else { DVec3::X };
+    let normal = unrotated_normal.reject_from(radial).normalize() * inclination.cos() + radial.cross(unrotated_normal).normalize() * inclination.sin();
+    primary_velocity + normal.cross(radial) * speed
+}
+
+// ############# Orbital Elements #############
+// osculating keplerian elements: the orbit a body would follow if only its primary attracted it
+// [Note: all angles are in radians; the reference plane is the xy plane and the reference direction is +x]
+#[derive(Reflect, Clone, Copy, Debug, Default, PartialEq)]
+pub struct OrbitalElements {
+    // negative for hyperbolic orbits (eccentricity above 1)
+    pub semi_major_axis: f64,
+    pub eccentricity: f64,
+    // 0 to 180°, above 90° the orbit is retrograde
+    pub inclination: f64,
+    // where the orbit goes up through the xy plane, 0 for orbits in the xy plane
+    pub longitude_of_ascending_node: f64,
+    // angle from the ascending node to the periapsis (from +x for orbits in the xy plane), 0 for circular orbits
+    pub argument_of_periapsis: f64,
+    // grows evenly with time, 0 at the periapsis
+    pub mean_anomaly: f64,
+}
+
+// below this eccentricity (or sin of the inclination) an orbit counts as circular (or as lying in the xy plane)
+const ELEMENTS_EPSILON: f64 = 1e-10;
+
+impl OrbitalElements {
+    // orbital period, infinite for orbits that are not closed
+    // gravitational_parameter: G * (mass of the primary + mass of the body)
+    pub fn period(&self, gravitational_parameter: f64) -> f64 {
+        if self.semi_major_axis > 0.0 && self.eccentricity < 1.0 && gravitational_parameter > 0.0 {
+            TAU * (self.semi_major_axis.powi(3) / gravitational_parameter).sqrt()
+        } else {
+            f64::INFINITY
+        }
+    }
+}
+
+// elements of the orbit of a body with the given position and velocity relative to its primary
+pub fn state_to_elements(position: DVec3, velocity: DVec3, gravitational_parameter: f64) -> OrbitalElements {
+    let distance = position.length();
+    let angular_momentum = position.cross(velocity);
+    let eccentricity_vector = ((velocity.length_squared() - gravitational_parameter / distance) * position - position.dot(velocity) * velocity) / gravitational_parameter;
+    let eccentricity = eccentricity_vector.length();
+    // vis-viva: v² = GM (2/r - 1/a)
+    let semi_major_axis = 1.0 / (2.0 / distance - velocity.length_squared() / gravitational_parameter);
+
+    // the angles are measured around the normal of the orbit, for orbits in the xy plane the node is put on +x
+    let normal = angular_momentum.normalize_or_zero();
+    // radial orbits (and bodies at rest) have no plane, the one closest to the xy plane is used (like the node below)
+    let radial = normal == DVec3::ZERO;
+    let normal = if radial { DVec3::Z.reject_from(position).try_normalize().unwrap_or(DVec3::X) } else { normal };
+    let inclination = normal.z.clamp(-1.0, 1.0).acos();
+    let node = DVec3::Z.cross(normal);
+    let node = if node.length() > ELEMENTS_EPSILON { node.normalize() } else { DVec3::X };
+    // for circular orbits the periapsis is put on the node
+    let periapsis = if eccentricity > ELEMENTS_EPSILON { eccentricity_vector / eccentricity } else { node };
+    let angle = |from: DVec3, to: DVec3| from.cross(to).dot(normal).atan2(from.dot(to)).rem_euclid(TAU);
+
+    let true_anomaly = angle(periapsis, position);
+    let mean_anomaly = if radial {
+        // the true anomaly is always 180° (the periapsis is the primary itself), the anomaly follows from the distance and the radial velocity instead
+        let radial_velocity = position.dot(velocity);
+        if semi_major_axis > 0.0 {
+            let eccentric_anomaly = (radial_velocity / (gravitational_parameter * semi_major_axis).sqrt()).atan2(1.0 - distance / semi_major_axis);
+            (eccentric_anomaly - eccentricity * eccentric_anomaly.sin()).rem_euclid(TAU)
+        } else {
+            let hyperbolic_anomaly = (radial_velocity / (-gravitational_parameter * semi_major_axis).sqrt() / eccentricity).asinh();
+            eccentricity * hyperbolic_anomaly.sinh() - hyperbolic_anomaly
+        }
+    } else if eccentricity < 1.0 {
+        let eccentric_anomaly = ((1.0 - eccentricity * eccentricity).sqrt() * true_anomaly.sin()).atan2(eccentricity + true_anomaly.cos());
+        (eccentric_anomaly - eccentricity * eccentric_anomaly.sin()).rem_euclid(TAU)
+    } else {
+        let hyperbolic_anomaly = ((eccentricity * eccentricity - 1.0).sqrt() * true_anomaly.sin() / (1.0 + eccentricity * true_anomaly.cos())).asinh();
+        eccentricity * hyperbolic_anomaly.sinh() - hyperbolic_anomaly
+    };
+
+    OrbitalElements {
+        semi_major_axis,
+        eccentricity,
+        inclination,
+        longitude_of_ascending_node: node.y.atan2(node.x).rem_euclid(TAU),
+        argument_of_periapsis: angle(node, periapsis),
+        mean_anomaly,
+    }
+}
+
+// position and velocity relative to the primary of a body on the orbit with the given elements (the inverse of state_to_elements)
+pub fn elements_to_state(elements: OrbitalElements, gravitational_parameter: f64) -> (DVec3, DVec3) {
+    let OrbitalElements { semi_major_axis, eccentricity, mean_anomaly, .. } = elements;
+
+    // kepler's equation (M = E - e sin E, or M = e sinh H - H for hyperbolic orbits) gets solved with newton's method
+    let true_anomaly = if eccentricity < 1.0 {
+        let mean_anomaly = mean_anomaly.rem_euclid(TAU);
+        let mut eccentric_anomaly = if eccentricity > 0.8 { PI } else { mean_anomaly };
+        for _ in 0..50 {
+            let correction = (eccentric_anomaly - eccentricity * eccentric_anomaly.sin() - mean_anomaly) / (1.0 - eccentricity * eccentric_anomaly.cos());
+            eccentric_anomaly -= correction;
+            if correction.abs() < 1e-15 {
+                break;
+            }
+        }
+        ((1.0 - eccentricity * eccentricity).sqrt() * eccentric_anomaly.sin()).atan2(eccentric_anomaly.cos() - eccentricity)
+    } else {
+        let mut hyperbolic_anomaly = (mean_anomaly / eccentricity).asinh();
+        for _ in 0..50 {
+            let correction = (eccentricity * hyperbolic_anomaly.sinh() - hyperbolic_anomaly - mean_anomaly) / (eccentricity * hyperbolic_anomaly.cosh() - 1.0);
+            hyperbolic_anomaly -= correction;
+            if correction.abs() < 1e-15 {
+                break;
+            }
+        }
+        2.0 * (((eccentricity + 1.0) / (eccentricity - 1.0)).sqrt() * (hyperbolic_anomaly / 2.0).tanh()).atan()
+    };
+
+    // position and velocity in the plane of the orbit (periapsis on +x), then rotated into place
+    let semi_latus_rectum = semi_major_axis * (1.0 - eccentricity * eccentricity);
+    let distance = semi_latus_rectum / (1.0 + eccentricity * true_anomaly.cos());
+    let position = DVec3::new(true_anomaly.cos(), true_anomaly.sin(), 0.0) * distance;
+    let velocity = DVec3::new(-true_anomaly.sin(), eccentricity + true_anomaly.cos(), 0.0) * (gravitational_parameter / semi_latus_rectum).sqrt();
+    let rotation = DQuat::from_rotation_z(elements.longitude_of_ascending_node)
+        * DQuat::from_rotation_x(elements.inclination)
+        * DQuat::from_rotation_z(elements.argument_of_periapsis);
+    (rotation * position, rotation * velocity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: DVec3, b: DVec3, tolerance: f64) {
+        assert!((a - b).length() <= tolerance * b.length().max(1.0), "{a} != {b}");
+    }
+
+    #[test]
+    fn state_elements_round_trip() {
+        let gravitational_parameter = 3.0e5;
+        for (position, velocity) in [
+            // inclined ellipse
+            (DVec3::new(800.0, -300.0, 120.0), DVec3::new(5.0, 17.0, -6.0)),
+            // in the xy plane, prograde and retrograde
+            (DVec3::new(-500.0, 200.0, 0.0), DVec3::new(-10.0, -22.0, 0.0)),
+            (DVec3::new(-500.0, 200.0, 0.0), DVec3::new(10.0, 22.0, 0.0)),
+            // circular
+            (DVec3::new(0.0, 1000.0, 0.0), DVec3::new(-(gravitational_parameter / 1000.0_f64).sqrt(), 0.0, 0.0)),
+            // hyperbolic
+            (DVec3::new(300.0, 100.0, -50.0), DVec3::new(-20.0, 45.0, 10.0)),
+        ] {
+            let elements = state_to_elements(position, velocity, gravitational_parameter);
+            let (new_position, new_velocity) = elements_to_state(elements, gravitational_parameter);
+            assert_close(new_position, position, 1e-9);
+            assert_close(new_velocity, velocity, 1e-9);
+        }
+    }
+
+    #[test]
+    fn elements_state_round_trip() {
+        let gravitational_parameter = 1.0;
+        let elements = OrbitalElements {
+            semi_major_axis: 2.5,
+            eccentricity: 0.3,
+            inclination: 0.4,
+            longitude_of_ascending_node: 1.2,
+            argument_of_periapsis: 2.1,
+            mean_anomaly: 4.0,
+        };
+        let (position, velocity) = elements_to_state(elements, gravitational_parameter);
+        let new_elements = state_to_elements(position, velocity, gravitational_parameter);
+        for (a, b) in [
+            (new_elements.semi_major_axis, elements.semi_major_axis),
+            (new_elements.eccentricity, elements.eccentricity),
+            (new_elements.inclination, elements.inclination),
+            (new_elements.longitude_of_ascending_node, elements.longitude_of_ascending_node),
+            (new_elements.argument_of_periapsis, elements.argument_of_periapsis),
+            (new_elements.mean_anomaly, elements.mean_anomaly),
+        ] {
+            assert!((a - b).abs() < 1e-10, "{a} != {b}");
+        }
+        // at the periapsis the distance is a (1 - e)
+        let (periapsis, _) = elements_to_state(OrbitalElements { mean_anomaly: 0.0, ..elements }, gravitational_parameter);
+        assert!((periapsis.length() - 2.5 * 0.7).abs() < 1e-12);
+    }
+
+    #[test]
+    fn radial_and_at_rest() {
+        let gravitational_parameter = 1.0;
+        let position = DVec3::new(3.0, 4.0, 0.0);
+        // falling straight down from rest: the body is at the apoapsis of a degenerate ellipse
+        let elements = state_to_elements(position, DVec3::ZERO, gravitational_parameter);
+        assert!((elements.semi_major_axis - 2.5).abs() < 1e-12);
+        assert!((elements.eccentricity - 1.0).abs() < 1e-12);
+        assert!(elements.inclination.abs() < 1e-12);
+        assert!((elements.mean_anomaly - PI).abs() < 1e-12);
+
+        for velocity in [position * 0.05, position * -0.05, position * 0.5] {
+            let elements = state_to_elements(position, velocity, gravitational_parameter);
+            let OrbitalElements { semi_major_axis, eccentricity, inclination, longitude_of_ascending_node, argument_of_periapsis, mean_anomaly } = elements;
+            for value in [semi_major_axis, eccentricity, inclination, longitude_of_ascending_node, argument_of_periapsis, mean_anomaly] {
+                assert!(value.is_finite(), "{elements:?}");
+            }
+            assert!((eccentricity - 1.0).abs() < 1e-12);
+            // on the way out the anomaly is between the periapsis and the apoapsis, on the way back after it
+            let outwards = velocity.dot(position) > 0.0;
+            if semi_major_axis > 0.0 {
+                assert_eq!(outwards, mean_anomaly < PI, "{elements:?}");
+            } else {
+                assert_eq!(outwards, mean_anomaly > 0.0, "{elements:?}");
+            }
+        }
+
+        // straight above the primary the plane can not contain +z
+        let elements = state_to_elements(DVec3::new(0.0, 0.0, 2.0), DVec3::new(0.0, 0.0, 0.1), gravitational_parameter);
+        assert!(elements.inclination.is_finite() && elements.mean_anomaly.is_finite(), "{elements:?}");
+    }
+
+    #[test]
+    fn orbit_velocity_matches_elements() {
+        let gravitational_constant = 2.0;
+        let (primary_mass, mass) = (1000.0, 1.0);
+        let position = DVec3::new(30.0, 40.0, 0.0);
+        for direction in OrbitDirection::ALL {
+            let shape = OrbitShape { eccentricity: 0.4, direction, inclination: 0.3 };
+            let velocity = orbit_velocity(position, DVec3::ZERO, DVec3::ZERO, primary_mass, mass, gravitational_constant, shape);
+            let elements = state_to_elements(position, velocity, gravitational_constant * (primary_mass + mass));
+            // the body starts at the periapsis
+            assert!((elements.semi_major_axis - 50.0 / 0.6).abs() < 1e-9);
+            assert!((elements.eccentricity - 0.4).abs() < 1e-12);
+            assert!(elements.mean_anomaly.min(TAU - elements.mean_anomaly) < 1e-9);
+            let inclination = if direction == OrbitDirection::Prograde { 0.3 } else { PI - 0.3 };
+            assert!((elements.inclination - inclination).abs() < 1e-12);
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use bevy::math::DVec3;
+
+// first order post-newtonian (1PN) correction to the acceleration caused by a body with the given mass
+// delta: position of the body - own position (same as in gravitational_acceleration)
+// relative_velocity: own velocity - velocity of the body
+// speed_of_light: c in the units of the simulation
+// this is the correction for a test particle around a mass (schwarzschild metric in harmonic coordinates), it makes the periapsis
+// of an orbit slowly turn around in the direction of the orbit (by 6πGM / (c² a (1 - e²)) per orbit, like the orbit of mercury)
+// [Note: for two bodies with similar masses the full 1PN two body equations would have extra terms, these get left out]
+pub fn post_newtonian_acceleration(delta: DVec3, relative_velocity: DVec3, mass: f64, gravitational_constant: f64, speed_of_light: f64) -> DVec3 {
+    let distance = delta.length();
+    if distance == 0.0 || speed_of_light <= 0.0 {
+        return DVec3::ZERO;
+    }
+    // from the attracting body to the own position
+    let r = -delta;
+    let gravitational_parameter = gravitational_constant * mass;
+    let factor = gravitational_parameter / (speed_of_light * speed_of_light * distance.powi(3));
+    factor * ((4.0 * gravitational_parameter / distance - relative_velocity.length_squared()) * r + 4.0 * r.dot(relative_velocity) * relative_velocity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{state_to_elements, Integrator, NBodySystem};
+    use super::*;
+
+    // argument of periapsis after the given number of orbits (integrated with RK4), starting at the apoapsis of an orbit with a = 1, e = 0.5
+    // [Note: the osculating argument of periapsis changes fastest close to the periapsis, so it gets compared at the apoapsis]
+    fn argument_of_periapsis_after(orbits: u32, post_newtonian: bool, speed_of_light: f64) -> f64 {
+        let (semi_major_axis, eccentricity) = (1.0, 0.5);
+        let mut system = NBodySystem {
+            integrator: Integrator::RungeKutta4,
+            gravitational_constant: 1.0,
+            post_newtonian,
+            speed_of_light,
+            ..NBodySystem::default()
+        };
+        let apoapsis = semi_major_axis * (1.0 + eccentricity);
+        system.add_body(DVec3::ZERO, DVec3::ZERO, 1.0);
+        system.add_body(DVec3::new(apoapsis, 0.0, 0.0), DVec3::new(0.0, ((1.0 - eccentricity) / apoapsis).sqrt(), 0.0), 0.0);
+
+        // G = M = a = 1, so one orbit takes 2π
+        let steps_per_orbit = 4000;
+        let dt = std::f64::consts::TAU / steps_per_orbit as f64;
+        for _ in 0..orbits * steps_per_orbit {
+            system.step(dt);
+        }
+        state_to_elements(system.positions[1] - system.positions[0], system.velocities[1] - system.velocities[0], 1.0).argument_of_periapsis
+    }
+
+    #[test]
+    fn periapsis_precession() {
+        let speed_of_light = 100.0;
+        let orbits = 10;
+        // the numerical error of the integrator (without the correction) gets subtracted
+        let precession = argument_of_periapsis_after(orbits, true, speed_of_light) - argument_of_periapsis_after(orbits, false, speed_of_light);
+        // 6πGM / (c² a (1 - e²)) per orbit
+        let expected = orbits as f64 * 6.0 * std::f64::consts::PI / (speed_of_light * speed_of_light * (1.0 - 0.5 * 0.5));
+        assert!((precession - expected).abs() <= 0.01 * expected, "{precession} != {expected}");
+    }
+}
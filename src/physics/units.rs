@@ -0,0 +1,246 @@
+use bevy::reflect::Reflect;
+use serde::{Deserialize, Serialize};
+
+use super::G;
+
+// ############# Unit System #############
+// what one unit of length, mass and time in the simulation means physically
+// [Note: the simulation itself only sees plain numbers, the gravitational constant gets scaled to match the units]
+#[derive(Reflect, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UnitSystem {
+    // 1 pixel = 1 m, 1 kg, 1 s (the values are small, but the forces are strong enough for nice looking orbits on the screen)
+    #[default]
+    Si,
+    // 1 pixel = 1000 km, 10^22 kg, 1 hour (planets with their moons)
+    PlanetMoon,
+    // 1 pixel = 10^6 km, 10^24 kg, 1 day (the inner solar system fits on the screen, 1 AU is about 150 pixels)
+    SolarSystem,
+}
+
+impl UnitSystem {
+    pub const ALL: [UnitSystem; 3] = [UnitSystem::Si, UnitSystem::PlanetMoon, UnitSystem::SolarSystem];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            UnitSystem::Si => "SI (1 px = 1 m)",
+            UnitSystem::PlanetMoon => "Planet + Moons (1 px = 1000 km)",
+            UnitSystem::SolarSystem => "Solar System (1 px = 10^6 km)",
+        }
+    }
+
+    // one simulation unit in meters, kilograms and seconds
+    pub fn length(&self) -> f64 {
+        match self {
+            UnitSystem::Si => 1.0,
+            UnitSystem::PlanetMoon => 1e6,
+            UnitSystem::SolarSystem => 1e9,
+        }
+    }
+
+    pub fn mass(&self) -> f64 {
+        match self {
+            UnitSystem::Si => 1.0,
+            UnitSystem::PlanetMoon => 1e22,
+            UnitSystem::SolarSystem => 1e24,
+        }
+    }
+
+    pub fn time(&self) -> f64 {
+        match self {
+            UnitSystem::Si => 1.0,
+            UnitSystem::PlanetMoon => 3600.0,
+            UnitSystem::SolarSystem => 86400.0,
+        }
+    }
+
+    // G in simulation units (G has the dimension length³ / (mass * time²))
+    pub fn gravitational_constant(&self) -> f64 {
+        G * self.mass() * self.time().powi(2) / self.length().powi(3)
+    }
+
+    // factor from simulation units to SI units for a quantity of the given dimension
+    pub fn to_si(&self, dimension: Dimension) -> f64 {
+        self.length().powi(dimension.length) * self.mass().powi(dimension.mass) * self.time().powi(dimension.time)
+    }
+}
+
+// ############# Dimension #############
+// exponents of length, mass, time and charge of a physical quantity (e.g. velocity is length¹ * time⁻¹)
+// [Note: charges are always in coulomb, no unit system scales them]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Dimension {
+    pub length: i32,
+    pub mass: i32,
+    pub time: i32,
+    pub charge: i32,
+}
+
+impl Dimension {
+    pub const LENGTH: Dimension = Dimension { length: 1, mass: 0, time: 0, charge: 0 };
+    pub const MASS: Dimension = Dimension { length: 0, mass: 1, time: 0, charge: 0 };
+    pub const TIME: Dimension = Dimension { length: 0, mass: 0, time: 1, charge: 0 };
+    pub const VELOCITY: Dimension = Dimension { length: 1, mass: 0, time: -1, charge: 0 };
+    pub const ACCELERATION: Dimension = Dimension { length: 1, mass: 0, time: -2, charge: 0 };
+    pub const DENSITY: Dimension = Dimension { length: -3, mass: 1, time: 0, charge: 0 };
+    pub const ENERGY: Dimension = Dimension { length: 2, mass: 1, time: -2, charge: 0 };
+    pub const MOMENTUM: Dimension = Dimension { length: 1, mass: 1, time: -1, charge: 0 };
+    pub const ANGULAR_MOMENTUM: Dimension = Dimension { length: 2, mass: 1, time: -1, charge: 0 };
+    pub const CHARGE: Dimension = Dimension { length: 0, mass: 0, time: 0, charge: 1 };
+    // k of the coulomb force F = k * q1 * q2 / r²
+    pub const COULOMB_CONSTANT: Dimension = Dimension { length: 3, mass: 1, time: -2, charge: -2 };
+    // plain numbers (e.g. exponents)
+    pub const NONE: Dimension = Dimension { length: 0, mass: 0, time: 0, charge: 0 };
+}
+
+// ############# Display Units #############
+#[derive(Reflect, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LengthUnit {
+    #[default]
+    Meter,
+    Kilometer,
+    AstronomicalUnit,
+}
+
+impl LengthUnit {
+    pub const ALL: [LengthUnit; 3] = [LengthUnit::Meter, LengthUnit::Kilometer, LengthUnit::AstronomicalUnit];
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            LengthUnit::Meter => "m",
+            LengthUnit::Kilometer => "km",
+            LengthUnit::AstronomicalUnit => "AU",
+        }
+    }
+
+    pub fn meters(&self) -> f64 {
+        match self {
+            LengthUnit::Meter => 1.0,
+            LengthUnit::Kilometer => 1e3,
+            LengthUnit::AstronomicalUnit => 1.495_978_707e11,
+        }
+    }
+}
+
+#[derive(Reflect, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MassUnit {
+    #[default]
+    Kilogram,
+    EarthMass,
+    SolarMass,
+}
+
+impl MassUnit {
+    pub const ALL: [MassUnit; 3] = [MassUnit::Kilogram, MassUnit::EarthMass, MassUnit::SolarMass];
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            MassUnit::Kilogram => "kg",
+            MassUnit::EarthMass => "M_earth",
+            MassUnit::SolarMass => "M_sun",
+        }
+    }
+
+    pub fn kilograms(&self) -> f64 {
+        match self {
+            MassUnit::Kilogram => 1.0,
+            MassUnit::EarthMass => 5.9722e24,
+            MassUnit::SolarMass => 1.98847e30,
+        }
+    }
+}
+
+#[derive(Reflect, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TimeUnit {
+    #[default]
+    Second,
+    Day,
+    Year,
+}
+
+impl TimeUnit {
+    pub const ALL: [TimeUnit; 3] = [TimeUnit::Second, TimeUnit::Day, TimeUnit::Year];
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            TimeUnit::Second => "s",
+            TimeUnit::Day => "d",
+            TimeUnit::Year => "yr",
+        }
+    }
+
+    pub fn seconds(&self) -> f64 {
+        match self {
+            TimeUnit::Second => 1.0,
+            TimeUnit::Day => 86400.0,
+            // julian year
+            TimeUnit::Year => 3.15576e7,
+        }
+    }
+}
+
+// units in which values get shown to (and typed in by) the user
+#[derive(Reflect, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DisplayUnits {
+    pub length: LengthUnit,
+    pub mass: MassUnit,
+    pub time: TimeUnit,
+}
+
+impl DisplayUnits {
+    // factor from these units to SI units for a quantity of the given dimension
+    pub fn to_si(&self, dimension: Dimension) -> f64 {
+        self.length.meters().powi(dimension.length) * self.mass.kilograms().powi(dimension.mass) * self.time.seconds().powi(dimension.time)
+    }
+
+    // unit of a quantity of the given dimension, e.g. "km/s²"
+    pub fn symbol(&self, dimension: Dimension) -> String {
+        let mut numerator = Vec::new();
+        let mut denominator = Vec::new();
+        for (symbol, exponent) in [
+            (self.mass.symbol(), dimension.mass),
+            (self.length.symbol(), dimension.length),
+            (self.time.symbol(), dimension.time),
+            ("C", dimension.charge),
+        ] {
+            let part = match exponent.abs() {
+                0 => continue,
+                1 => symbol.to_string(),
+                2 => format!("{symbol}²"),
+                3 => format!("{symbol}³"),
+                n => format!("{symbol}^{n}"),
+            };
+            if exponent > 0 { numerator.push(part) } else { denominator.push(part) }
+        }
+        // plain numbers have no unit
+        if numerator.is_empty() && denominator.is_empty() {
+            return String::new();
+        }
+        let numerator = if numerator.is_empty() { String::from("1") } else { numerator.join("·") };
+        if denominator.is_empty() { numerator } else { format!("{}/{}", numerator, denominator.join("·")) }
+    }
+}
+
+// ############# Units #############
+// simulation units and display units together, converts between them
+#[derive(Reflect, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Units {
+    pub system: UnitSystem,
+    pub display: DisplayUnits,
+}
+
+impl Units {
+    // simulation value -> value in display units
+    pub fn to_display(&self, value: f64, dimension: Dimension) -> f64 {
+        value * self.system.to_si(dimension) / self.display.to_si(dimension)
+    }
+
+    // value in display units -> simulation value
+    pub fn to_simulation(&self, value: f64, dimension: Dimension) -> f64 {
+        value * self.display.to_si(dimension) / self.system.to_si(dimension)
+    }
+
+    // formats a simulation value in display units, e.g. "1.234e3 km/s"
+    pub fn format(&self, value: f64, dimension: Dimension) -> String {
+        format!("{:.4e} {}", self.to_display(value, dimension), self.display.symbol(dimension))
+    }
+}
@@ -1,56 +1,350 @@
-use bevy::{input::common_conditions::input_toggle_active, prelude::*, window::PrimaryWindow};
+use std::collections::{HashMap, HashSet};
 
-use crate::{world::{Game, MyCamera}, GameState};
+use bevy::{input::common_conditions::input_toggle_active, math::DVec3, prelude::*, tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task}, window::PrimaryWindow};
+use bevy_inspector_egui::bevy_egui::{egui, EguiContext};
+use serde::{Deserialize, Serialize};
 
-use std::time::{Duration, Instant};
-
-use std::collections::HashMap;
+use crate::{particles::TestParticle, physics::{bounce, collision_groups, find_collisions, merge_bodies, orbit_velocity, state_to_elements, BodyInputs, BodyProperties, Charged, CollisionMode, Dimension, ForceLaw, ForceSolver, Integrator, NBodySystem, Newtonian, OrbitalElements, PowerLaw, Yukawa}, world::{Game, GameState, OrbitCamera, OrbitPrimary, ReferenceFrame, SpawnMode, ViewMode}};
 
 pub struct PlanetPlugin;
 
-const G: f32 = 6.67430e-11;
-
 impl Plugin for PlanetPlugin {
     fn build(&self, app: &mut App) {
         app
-        .add_systems(Startup, (setup, setup_gizmo_config))
+        .add_systems(Startup, (setup, setup_gizmo_config, setup_planet_sphere))
         // load update systems [Note: these are only running if GameState is set to Running]
         // .add_systems(Update, planet_movement.run_if(in_state(GameState::Running)))
         // add systems that get called every frame [Note: these are always running]
-        .add_systems(Update, (update_planet_size, spawn_new_planets, clicked_on_planet))
+        .add_systems(Update, (update_planet_size, spawn_new_planets, clicked_on_planet, sync_planet_transforms, add_planet_spheres))
         // load future planet movement drawing system, but only if GameState is set to Paused
-        .add_systems(Update, draw_future_planet_movement.run_if(input_toggle_active(true, KeyCode::Tab)))
+        .add_systems(Update, (update_path_prediction, draw_future_planet_movement, draw_predicted_events).chain().run_if(input_toggle_active(true, KeyCode::Tab)))
         // add physics system to FixedUpdate in order to get a more stable simulation
-        .add_systems(FixedUpdate, (update_velocity, planet_movement).chain().run_if(in_state(GameState::Running)))
+        .add_systems(FixedUpdate, (update_physics, handle_collisions).chain().in_set(PhysicsStep).run_if(in_state(GameState::Running)))
         // transfer initial velocity to current velocity, but only if GameState is set from anything to Running
         .add_systems(OnEnter(GameState::Running), set_initial_velocity)
 
         // register type Planet to display and edit it in the WorldInspector
         .register_type::<Planet>()
+        // holds the sub step size of adaptive integrators between FixedUpdate ticks
+        .init_resource::<AdaptiveStep>()
+        // all force laws that can be picked in the UI (other crates can add their own with add_force_law)
+        .init_resource::<ForceLaws>()
+        // cached paths of the path prediction (and the calculation running in the background)
+        .init_resource::<PathPrediction>()
         // init future planet movement gizmos for drawing lines
         .init_gizmo_group::<FutuerPlanetMovementGizmos>()
         ;
     }
 }
 
+// ############# Physics Step System Set #############
+// all systems that advance the simulation in FixedUpdate (so other plugins can run their systems before or after them)
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PhysicsStep;
+
 // ############# Planet Label Component #############
 #[derive(Component)]
-struct PlanetLabel;
+pub(crate) struct PlanetLabel;
+
+// ############# Planet Sphere #############
+// sphere that gets drawn by the 3D camera (the 2D camera only draws the sprite and the label)
+#[derive(Resource)]
+struct PlanetSphere {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+fn setup_planet_sphere(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>
+) {
+    commands.insert_resource(PlanetSphere {
+        // same size as the planet in the sprite, so it gets the radius of the planet with the scale of the sprite (see update_planet_size)
+        mesh: meshes.add(Sphere::new(512.0).mesh().uv(32, 18)),
+        material: materials.add(StandardMaterial {
+            base_color: Color::rgb(0.55, 0.65, 0.9),
+            perceptual_roughness: 0.8,
+            ..default()
+        }),
+    });
+}
+
+// every new planet gets a sphere as a child
+fn add_planet_spheres(
+    mut commands: Commands,
+    planet_q: Query<Entity, Added<Planet>>,
+    sphere: Res<PlanetSphere>
+) {
+    for entity in planet_q.iter() {
+        commands.entity(entity).with_children(|builder| {
+            builder.spawn(PbrBundle {
+                mesh: sphere.mesh.clone(),
+                material: sphere.material.clone(),
+                ..default()
+            });
+        });
+    }
+}
 
 // ############# Planet Component #############
 #[derive(Reflect, Component, Default)]
 #[reflect(Component)]
+// [Note: the physics state is stored in double precision; the Transform only gets a copy of the position for rendering (see sync_planet_transforms)]
+// [Note: mass and radius are what the simulation uses, density and surface gravity get derived from them (see properties)]
 pub struct Planet {
-    pub gravity: f32,
-    pub velocity: Vec3,
-    pub acceleration: Vec3,
-    pub radius: f32,
-    pub initial_velocity: Vec3,
+    pub mass: f64,
+    pub position: DVec3,
+    pub velocity: DVec3,
+    pub acceleration: DVec3,
+    pub radius: f64,
+    pub initial_velocity: DVec3,
+    // which two properties get edited in the UI (the other two are derived)
+    pub inputs: BodyInputs,
+    // pinned planets dont move, but still attract all others
+    pub pinned: bool,
+    // only used by force laws with charges (see ForceLaw::uses_charge)
+    pub charge: f64,
+    // color of the number label and the predicted path (set in spawn_planet)
+    pub color: Color,
+    // the path gets still calculated (it influences the others), but not drawn
+    pub hide_path: bool,
 }
 
 impl Planet {
-    pub fn mass(&self) -> f32 {
-        self.gravity * self.radius.powi(2) / G
+    // gravitational_constant: G in simulation units (see UnitSystem)
+    pub fn properties(&self, gravitational_constant: f64) -> BodyProperties {
+        BodyProperties::from_mass_radius(self.mass, self.radius, gravitational_constant)
+    }
+
+    // velocity the planet has once the simulation runs (the initial velocity only gets applied when it starts)
+    pub fn start_velocity(&self) -> DVec3 {
+        if self.pinned { DVec3::ZERO } else { self.velocity + self.initial_velocity }
+    }
+}
+
+// ############# Adaptive Step Resource #############
+// adaptive integrators pick their own sub step size, it gets stored here so the next tick can continue with it
+// (and so it can be displayed in the UI)
+#[derive(Resource, Default)]
+pub struct AdaptiveStep {
+    pub step_size: f64,
+    // running total since the start (NBodySystem::rejected_steps only counts the last step)
+    pub rejected_steps: u32,
+}
+
+// ############# Force Laws Resource #############
+// all force laws that can be picked in the UI, and the index of the active one
+#[derive(Resource)]
+pub struct ForceLaws {
+    laws: Vec<Box<dyn ForceLaw>>,
+    active: usize,
+}
+
+impl Default for ForceLaws {
+    fn default() -> Self {
+        Self {
+            laws: vec![Box::new(Newtonian), Box::new(PowerLaw::default()), Box::new(Yukawa::default()), Box::new(Charged::default())],
+            active: 0,
+        }
+    }
+}
+
+impl ForceLaws {
+    // adds a force law to the list and returns its index (it doesnt get selected)
+    pub fn register(&mut self, force_law: impl ForceLaw + 'static) -> usize {
+        self.laws.push(Box::new(force_law));
+        self.laws.len() - 1
+    }
+
+    // makes the force law with the given index the active one (invalid indices get ignored)
+    pub fn select(&mut self, index: usize) {
+        if index < self.laws.len() {
+            self.active = index;
+        }
+    }
+
+    pub fn active_index(&self) -> usize {
+        self.active
+    }
+
+    pub fn active(&self) -> &dyn ForceLaw {
+        self.laws[self.active].as_ref()
+    }
+
+    pub fn active_mut(&mut self) -> &mut dyn ForceLaw {
+        self.laws[self.active].as_mut()
+    }
+
+    // index and name of every registered force law
+    pub fn names(&self) -> Vec<(usize, String)> {
+        self.laws.iter().enumerate().map(|(index, force_law)| (index, force_law.name().to_string())).collect()
+    }
+}
+
+// lets other crates add their own force law: app.add_force_law(MyForceLaw)
+// [Note: works before and after the PlanetPlugin was added, the resource gets created if it doesnt exist yet]
+pub trait AddForceLaw {
+    fn add_force_law(&mut self, force_law: impl ForceLaw + 'static) -> &mut Self;
+}
+
+impl AddForceLaw for App {
+    fn add_force_law(&mut self, force_law: impl ForceLaw + 'static) -> &mut Self {
+        self.world.get_resource_or_insert_with(ForceLaws::default).register(force_law);
+        self
+    }
+}
+
+// ############# Path Prediction Resource #############
+// the predicted paths get calculated in the background (on the AsyncComputeTaskPool) and are only recalculated when their input changes
+#[derive(Resource, Default)]
+struct PathPrediction {
+    // input of the last started calculation
+    input: PredictionInput,
+    // calculation that is running at the moment
+    task: Option<Task<PredictedPaths>>,
+    // result of the last finished calculation and its input, this gets drawn until the next one is done
+    result: PredictedPaths,
+    result_input: PredictionInput,
+    // false if the result doesnt fit to the planets and settings anymore (then it doesnt get drawn)
+    result_current: bool,
+}
+
+#[derive(Default)]
+struct PredictedPaths {
+    // planet and the points of its path
+    paths: Vec<(Entity, Vec<Vec3>)>,
+    // center of mass of all planets at the same points in time (for ReferenceFrame::Barycenter)
+    barycenter: Vec<Vec3>,
+    // collisions and close approaches, sorted by time
+    events: Vec<PredictedEvent>,
+}
+
+// collision or close approach of two planets found by the prediction
+#[derive(Clone)]
+struct PredictedEvent {
+    kind: PredictedEventKind,
+    // indices of the two planets in the prediction
+    bodies: (usize, usize),
+    // time until it happens and distance between the centers of the planets
+    time: f64,
+    distance: f64,
+    // positions of the two planets at that time, and of the reference frame the prediction was made for (see PredictionInput::frame)
+    positions: (Vec3, Vec3),
+    origin: Vec3,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PredictedEventKind {
+    // the planets touch (only the first collision of two planets gets reported, the prediction lets them pass through each other)
+    Collision,
+    // closest point of a pass closer than PredictionSettings::close_approach_distance
+    CloseApproach,
+}
+
+// everything the prediction depends on
+// [Note: the values get compared instead of using change detection, because the UI marks the planets and the Game as changed every frame;
+// the Transform is only a copy of the position for rendering, so it doesnt matter here]
+#[derive(Clone, Default, PartialEq)]
+struct PredictionInput {
+    bodies: Vec<PredictionBody>,
+    system: PredictionSystem,
+    stepsize: f64,
+    prediction: PredictionSettings,
+    // the events only store the position of the reference frame, the paths themselves can be drawn in every frame
+    frame: ReferenceFrame,
+}
+
+impl PredictionInput {
+    // true if other only differs because the simulation moved on: the planets move along the predicted paths, so the prediction still fits
+    fn moved_on(&self, other: &PredictionInput) -> bool {
+        self.system == other.system && self.stepsize == other.stepsize && self.prediction == other.prediction && self.frame == other.frame
+        && self.bodies.len() == other.bodies.len()
+        && self.bodies.iter().zip(other.bodies.iter()).all(|(a, b)| {
+            a.entity == b.entity && a.mass == b.mass && a.charge == b.charge && a.radius == b.radius && a.pinned == b.pinned
+        })
+    }
+
+    // true if a prediction made for self can be used for other
+    fn fits(&self, other: &PredictionInput, running: bool) -> bool {
+        self == other || (running && self.moved_on(other))
+    }
+}
+
+// settings of the NBodySystem (see new_system)
+#[derive(Clone, Default, PartialEq)]
+struct PredictionSystem {
+    integrator: Integrator,
+    tolerance: f64,
+    force_solver: ForceSolver,
+    opening_angle: f64,
+    parallel: bool,
+    softening: f64,
+    gravitational_constant: f64,
+    post_newtonian: bool,
+    speed_of_light: f64,
+    // the force law itself can not be compared, but its name and the values of its parameters
+    force_law: String,
+    parameters: Vec<(&'static str, f64)>,
+}
+
+impl PredictionSystem {
+    fn new(system: &NBodySystem) -> Self {
+        Self {
+            integrator: system.integrator,
+            tolerance: system.tolerance,
+            force_solver: system.force_solver,
+            opening_angle: system.opening_angle,
+            parallel: system.parallel,
+            softening: system.softening,
+            gravitational_constant: system.gravitational_constant,
+            post_newtonian: system.post_newtonian,
+            speed_of_light: system.speed_of_light,
+            force_law: system.force_law.name().to_string(),
+            parameters: system.force_law.parameters().into_iter().map(|(name, value, _)| (name, value)).collect(),
+        }
+    }
+}
+
+// state of a planet at the start of the prediction
+#[derive(Clone, PartialEq)]
+struct PredictionBody {
+    entity: Entity,
+    position: DVec3,
+    // including the initial velocity
+    velocity: DVec3,
+    mass: f64,
+    charge: f64,
+    radius: f64,
+    pinned: bool,
+}
+
+// ############# Prediction Settings #############
+// how far into the future and how detailed the paths get predicted
+#[derive(Reflect, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct PredictionSettings {
+    // in simulation time
+    pub horizon: f64,
+    // only every n-th step becomes a point of the path (fewer points are faster to draw, the steps stay the same)
+    pub sample_interval: u32,
+    // planets that pass each other closer than this (distance between the centers) get marked, 0 turns it off
+    pub close_approach_distance: f64,
+}
+
+impl Default for PredictionSettings {
+    fn default() -> Self {
+        // 5000 steps with the step size of 0.1 (see update_fixed_timestep)
+        Self { horizon: 500.0, sample_interval: 1, close_approach_distance: 400.0 }
+    }
+}
+
+// longer predictions take a while and need a lot of memory for the points
+pub(crate) const MAX_PREDICTION_STEPS: u32 = 200_000;
+
+impl PredictionSettings {
+    // number of steps needed to reach the horizon (at most MAX_PREDICTION_STEPS, a horizon typed in as time can be much further away)
+    pub fn steps(&self, stepsize: f64) -> u32 {
+        if stepsize > 0.0 { (self.horizon / stepsize).ceil().clamp(0.0, MAX_PREDICTION_STEPS as f64) as u32 } else { 0 }
     }
 }
 
@@ -59,43 +353,81 @@ impl Planet {
 struct FutuerPlanetMovementGizmos {}
 
 // ############# Helper Functions #############
-fn spawn_planet(mut commands: Commands, asset_server: &AssetServer, game: &mut Game, position: Vec3) {
+// creates an empty NBodySystem with the simulation settings of the game
+// [Note: used by the live simulation and the path prediction, so both behave the same]
+pub(crate) fn new_system(game: &Game, force_laws: &ForceLaws) -> NBodySystem {
+    NBodySystem {
+        force_law: force_laws.active().clone_box(),
+        integrator: game.integrator,
+        tolerance: game.tolerance,
+        force_solver: game.force_solver,
+        opening_angle: game.opening_angle,
+        parallel: game.parallel,
+        softening: game.softening,
+        gravitational_constant: game.units.system.gravitational_constant(),
+        post_newtonian: game.post_newtonian,
+        speed_of_light: game.speed_of_light,
+        ..default()
+    }
+}
+
+// planet that gets spawned with a right click: at rest, with a radius of 100 and a surface gravity of 10 (in simulation units)
+pub(crate) fn default_planet(game: &Game, position: DVec3) -> Planet {
+    let properties = BodyProperties::from_inputs(BodyInputs::RadiusSurfaceGravity, 100.0, 10.0, game.units.system.gravitational_constant());
+    Planet {
+        mass: properties.mass,
+        position,
+        radius: properties.radius,
+        ..default()
+    }
+}
+
+// texture of every planet (1280x1280, see update_planet_size)
+pub(crate) const PLANET_TEXTURE: &str = "planet00.png";
+
+// spawns the planet with its sprite and number label, without a name it gets called "Planet <number>"
+pub(crate) fn spawn_planet(commands: &mut Commands, asset_server: &AssetServer, game: &mut Game, mut planet: Planet, name: Option<String>) -> Entity {
     // we added 1 planet, so we add 1 to the global planet count
     game.planet_count += 1;
+    // every planet gets its own color: the hue moves by the golden angle, so following planets never look alike
+    planet.color = Color::hsl((game.planet_count as f32 * 137.5) % 360.0, 0.8, 0.6);
 
     // define scale for the planet
-    // default planet size is 1024x1024, so with 0.05 scale it is 51.2x51.2
+    // [Note: only for the first frame, update_planet_size sets the scale from the radius of the planet]
     let scale = 0.05;
+    // transform describes the position, scale, and rotation of the sprite
+    let transform = Transform {
+        // set the position to the position of the planet
+        translation: planet.position.as_vec3(),
+        // set the image to a prescaled size
+        scale: Vec3::new(scale, scale, 1.0),
+        // loads all the default values (rotation set to 0)
+        ..default()
+    };
+    let name = name.unwrap_or(format!("Planet {}", game.planet_count));
+    spawn_planet_entity(commands, asset_server, planet, name, game.planet_count.to_string(), PLANET_TEXTURE, transform)
+}
+
+// spawns the planet exactly as given (spawn_planet picks the number, color and the rest for a new planet, loading a scenario restores them)
+pub(crate) fn spawn_planet_entity(commands: &mut Commands, asset_server: &AssetServer, planet: Planet, name: String, label: String, texture: &str, transform: Transform) -> Entity {
+    let label_color = planet.color;
 
     // spawn Planet
     commands.spawn(
         (
             // with a Sprite bundle (for all the visual stuff)
             SpriteBundle {
-                // transform describes the position, scale, and rotation of the sprite
-                transform: Transform {
-                    // set the position to the center of the window
-                    translation: position,
-                    // set the image to a prescaled size
-                    scale: Vec3::new(scale, scale, 1.0),
-                    // loads all the default values (rotation set to 0)
-                    ..default()
-                },
+                transform,
                 // load texture with asset server (automatically looks in the assets folder for the file name provided)
-                texture: asset_server.load("planet00.png"),
+                texture: asset_server.load(texture.to_string()),
                 // again, load all the default values [Note: hover over SpriteBundle to see all the values you can set here]
                 ..default()
             },
             // and a Planet, so it is marked as a planet 
             // (also holds some data about the planet [Q: is this the best way to do this? Or should gravity, velocity, etc. all be separate Components?])
-            Planet {
-                gravity: 10.,
-                velocity: Vec3::new(0., 0., 0.),
-                radius: 100.0,
-                ..default()
-            },
+            planet,
             // Give it a name so it is easily identifiable in the WorldInspector
-            Name::new(format!("Planet {}", game.planet_count))
+            Name::new(name)
         )
     // spawn one children, a Text2dBundle, to display the number of the planet
     ).with_children(|builder|{
@@ -103,10 +435,10 @@ fn spawn_planet(mut commands: Commands, asset_server: &AssetServer, game: &mut G
             (
                 Text2dBundle {
                     text: Text::from_section(
-                        game.planet_count.to_string(), 
+                        label, 
                         TextStyle {
                             font_size: 600.0,
-                            color: Color::GREEN,
+                            color: label_color,
                             ..default()
                         }
                     ),
@@ -119,20 +451,21 @@ fn spawn_planet(mut commands: Commands, asset_server: &AssetServer, game: &mut G
                 PlanetLabel {}
             )
         );
-    });
+    }).id()
 }
 
 // ############# Systems #############
 // ############# Startup Systems #############
 fn setup (
     // get commands to spawn entities
-    commands: Commands,
+    mut commands: Commands,
     // getting the asset server to loading textures
     asset_server: Res<AssetServer>,
     mut game: ResMut<Game>
 ) {
     // spawn the first planet at the center of the window
-    spawn_planet(commands, asset_server.as_ref(), game.as_mut(), Vec3::new(0., 0., 0.));
+    let planet = default_planet(&game, DVec3::new(0., 0., 0.));
+    spawn_planet(&mut commands, asset_server.as_ref(), game.as_mut(), planet, None);
 
 }
 
@@ -145,49 +478,138 @@ fn setup_gizmo_config (
 }
 
 // ############# Update Systems: GameState::Running #############
-// calculate the velocity of the planets based on their current velocity, mass and distance
-fn update_velocity(
-    mut planet_q: Query<(&mut Transform, &mut Planet)>,
-    game: Res<Game>
+// advances all planets by one step: the state gets copied into an NBodySystem, stepped there and copied back
+fn update_physics(
+    mut planet_q: Query<&mut Planet>,
+    mut particle_q: Query<&mut TestParticle>,
+    game: Res<Game>,
+    force_laws: Res<ForceLaws>,
+    mut adaptive_step: ResMut<AdaptiveStep>
 ) {
-
-    let mut bodies: Vec<(Mut<Transform>, Mut<Planet>)> = Vec::new();
-
-    for (transform, mut planet) in planet_q.iter_mut() {
-        for (other_transform, other_planet) in bodies.iter_mut() {
-            let sqr_dst = (other_transform.translation - transform.translation).length_squared();
-            let force_dir = (other_transform.translation - transform.translation).normalize();
-            // force is the same for both planets
-            let force = force_dir * G * (planet.mass() * other_planet.mass()) / sqr_dst;
-            // acceleration differs based on mass
-            planet.acceleration = force / planet.mass();
-            let planet_acceleration = force / planet.mass();
-            // movement is only dependent on stepsize: this is fixed, because the whole system gets updated in FixedUpdate, which frequency gets
-            // set by the Game.speed value [in world.rs:55]
-            planet.velocity += planet_acceleration * game.stepsize;
-
-            // same force is applied, just in the opposite direction
-            other_planet.acceleration = -force / other_planet.mass();
-            let other_planet_acceleration = -force / other_planet.mass();
-            other_planet.velocity += other_planet_acceleration * game.stepsize;
+    let mut system = new_system(&game, &force_laws);
+    system.adaptive_step = adaptive_step.step_size;
+    for planet in planet_q.iter() {
+        let index = system.add_body(planet.position, planet.velocity, planet.mass);
+        system.set_charge(index, planet.charge);
+        if planet.pinned {
+            system.pin(index);
         }
-        bodies.push((transform, planet))
+    }
+    // test particles are massless bodies after the planets, they get moved by the planets but dont pull on anything
+    let first_particle = system.len();
+    for particle in particle_q.iter() {
+        system.add_body(particle.position, particle.velocity, 0.0);
+    }
+
+    // movement is only dependent on stepsize: this is fixed, because the whole system gets updated in FixedUpdate, which frequency gets
+    // set by the Game.speed value [in world.rs]
+    system.step(game.stepsize);
+
+    if game.integrator.is_adaptive() {
+        adaptive_step.step_size = system.adaptive_step;
+        adaptive_step.rejected_steps += system.rejected_steps;
+    }
+
+    // the query yields the planets in the same order as above, so the index matches the one in the system
+    for (i, mut planet) in planet_q.iter_mut().enumerate() {
+        planet.position = system.positions[i];
+        planet.velocity = system.velocities[i];
+        planet.acceleration = system.accelerations[i];
+    }
+    for (i, mut particle) in particle_q.iter_mut().enumerate() {
+        particle.position = system.positions[first_particle + i];
+        particle.velocity = system.velocities[first_particle + i];
     }
 }
 
-// updates the position of the planets based on their velocity
-fn planet_movement (
+// copies the (double precision) position of the planets into their Transform, which is only used for rendering
+fn sync_planet_transforms(
     mut query: Query<(&mut Transform, &Planet)>,
     game: Res<Game>
 ) {
-
     for (mut transform, planet) in query.iter_mut() {
-        transform.translation += planet.velocity * game.stepsize;
+        // in 2D the planets are seen from above, z would only decide what gets drawn on top (and could move them out of the camera range)
+        let translation = match game.view_mode {
+            ViewMode::TwoD => planet.position.as_vec3().truncate().extend(0.0),
+            ViewMode::ThreeD => planet.position.as_vec3(),
+        };
+        // only write on changes, so change detection on the Transform stays meaningful
+        if transform.translation != translation {
+            transform.translation = translation;
+        }
     }
+}
+
+// detects overlapping planets and merges them or lets them bounce off each other (depending on Game.collision_mode)
+fn handle_collisions(
+    mut commands: Commands,
+    mut planet_q: Query<(Entity, &mut Planet, &mut Name)>,
+    game: Res<Game>
+) {
+    if game.collision_mode == CollisionMode::Ignore {
+        return;
+    }
+
+    let positions = planet_q.iter().map(|(_, planet, _)| planet.position).collect::<Vec<_>>();
+    let radii = planet_q.iter().map(|(_, planet, _)| planet.radius).collect::<Vec<_>>();
+    let collisions = find_collisions(&positions, &radii);
+    if collisions.is_empty() {
+        return;
+    }
+
+    match game.collision_mode {
+        CollisionMode::Ignore => {}
+        CollisionMode::Bounce => {
+            let masses = planet_q.iter().map(|(_, planet, _)| planet.mass).collect::<Vec<_>>();
+            let pinned = planet_q.iter().map(|(_, planet, _)| planet.pinned).collect::<Vec<_>>();
+            let mut positions = positions;
+            let mut velocities = planet_q.iter().map(|(_, planet, _)| planet.velocity).collect::<Vec<_>>();
+            bounce(&mut positions, &mut velocities, &masses, &pinned, &radii, &collisions, game.restitution);
+            for ((_, mut planet, _), (position, velocity)) in planet_q.iter_mut().zip(positions.into_iter().zip(velocities)) {
+                planet.position = position;
+                planet.velocity = velocity;
+            }
+        }
+        CollisionMode::Merge => {
+            let entities = planet_q.iter().map(|(entity, _, _)| entity).collect::<Vec<_>>();
+            for group in collision_groups(entities.len(), &collisions) {
+                // the heaviest planet survives, all others get despawned
+                let bodies = group.iter().filter_map(|i| planet_q.get(entities[*i]).ok()).map(|(entity, planet, name)| {
+                    (entity, planet.position, planet.velocity, planet.mass, planet.radius, name.to_string())
+                }).collect::<Vec<_>>();
+                // if a pinned planet is part of the collision, the merged planet stays pinned where it is
+                // the charge of the merged planet is the sum of all charges
+                let charge = group.iter().filter_map(|i| planet_q.get(entities[*i]).ok()).map(|(_, planet, _)| planet.charge).sum::<f64>();
+                let pinned_position = group.iter().filter_map(|i| planet_q.get(entities[*i]).ok()).find(|(_, planet, _)| planet.pinned).map(|(_, planet, _)| planet.position);
+                let Some(survivor) = bodies.iter().max_by(|a, b| a.3.total_cmp(&b.3)).map(|body| body.0) else {
+                    continue;
+                };
+                let merged = merge_bodies(bodies.iter().map(|(_, position, velocity, mass, radius, _)| (*position, *velocity, *mass, *radius)));
+                let names = bodies.iter().map(|body| body.5.as_str()).collect::<Vec<_>>().join(" + ");
 
+                for (entity, ..) in bodies.iter() {
+                    if *entity != survivor {
+                        commands.entity(*entity).despawn_recursive();
+                    }
+                }
+                if let Ok((_, mut planet, mut name)) = planet_q.get_mut(survivor) {
+                    planet.position = pinned_position.unwrap_or(merged.position);
+                    planet.velocity = if pinned_position.is_some() { DVec3::ZERO } else { merged.velocity };
+                    planet.radius = merged.radius;
+                    planet.mass = merged.mass;
+                    planet.pinned = pinned_position.is_some();
+                    planet.charge = charge;
+                    *name = Name::new(names);
+                }
+            }
+        }
+    }
 }
 
 // sets planet and label size based on radius
+// smallest radius (in pixels on the screen) a planet gets drawn with
+const MIN_PLANET_SCREEN_RADIUS: f32 = 4.0;
+
 fn update_planet_size(
     mut query: Query<(&mut Transform, &Planet, &Children)>,
     mut child_q: Query<&mut Text, With<PlanetLabel>>,
@@ -207,110 +629,303 @@ fn update_planet_size(
             }
         });
         // set the scale of the planet sprite to the radius of the planet
-        transform.scale = Vec3::splat(planet.radius/1024.0);
+        // [Note: the texture is 1280px wide and the planet in it about 1024px, so with this scale the drawn planet has the radius used for collisions]
+        // planets with realistic sizes (e.g. in the solar system units) would be far too small to see, so they never get smaller than a few pixels
+        let radius = (planet.radius as f32).max(MIN_PLANET_SCREEN_RADIUS * query_camera.single().scale);
+        transform.scale = Vec3::splat(radius/512.0);
     }
 }
 // ############# Update Systems: GameState::Paused #############
-// This can get a lot more performant
-fn draw_future_planet_movement (
-    mut gizmos: Gizmos,
-    planet_q: Query<(&Transform, Ref<Planet>, &Name)>,
-    game: Res<Game>
+// starts a new calculation of the paths in the background when something changed, and picks up the result once it is done
+// [Note: only one calculation runs at a time; if something gets changed while it runs, it gets cancelled and the new one starts right away.
+// while the game runs the planets change every tick, so then the calculation gets finished first (and the next one starts right after it)]
+fn update_path_prediction(
+    mut prediction: ResMut<PathPrediction>,
+    planet_q: Query<(Entity, &Planet)>,
+    game: Res<Game>,
+    force_laws: Res<ForceLaws>,
+    game_state: Res<State<GameState>>
 ) {
-    // TODO: Currently all the calculations are done every frame, this is not necessary when the velocity of the planets dont change
-
-    // ############# Time Measure #############
-    let mut _now = Instant::now();
-    let _elapsed1: Duration;
-    let mut _elapsed2: Duration = Duration::new(0, 0);
-    let mut _elapsed3: Duration = Duration::new(0, 0);
-    let mut _elapsed4: Duration = Duration::new(0, 0);
-    // ########################################
-
-    // transform all planets to virtual planets
-    let mut virtual_planets = planet_q.iter().map(|(transform, planet, name)| {
-        let virtual_transform = transform.clone();
-        let virtual_planet_velocity = planet.velocity + planet.initial_velocity;
-        let virtual_planet_mass = planet.mass();
-        let virtual_planet_name = name.to_string();
-        // virtual_planet_velocity = planet.velocity;
-        (virtual_transform, virtual_planet_velocity, virtual_planet_mass, virtual_planet_name)
-    }).collect::<Vec<_>>();
-
-    // ############# Time Measure #############
-    _elapsed1 = _now.elapsed();
-    // ########################################
-
-    // points to draw [TODO: make this dynamic, so more than the path of two planets can be drawn]
-    let mut points = HashMap::<String, Vec<Vec2>>::new();
-
-    // defines how long in the future we can see
-    // [TODO: Maybe make a field in the WorldInspector to change this value]
-    for _ in 0..5000 {
-
-        // ############# Time Measure #############
-        _now = Instant::now();
-        // ########################################
-
-        let mut bodies: Vec<(Transform, (Vec3, f32), String)> = Vec::new();
-        // use clone here to satisfy borrow checker; altough virtual_planets gets overwritten later
-        // it is crucial to consume the values here, otherwise we would not be able to set the velocity of the "other_planets" 
-        for (virtual_transform, mut virtual_planet_velocity, virtual_planet_mass, virtual_planet_name) in virtual_planets.clone().into_iter() {
-            // we dont need to consume bodies here, a mutable reference will do just fine
-            for (other_virtual_transform, (other_virtual_planet_velocity, other_virtual_planet_mass ), _other_name) in bodies.iter_mut() {
-                let sqr_dst = (other_virtual_transform.translation - virtual_transform.translation).length_squared();
-                let force_dir = (other_virtual_transform.translation - virtual_transform.translation).normalize();
-                // calculate the force between the two planets [same for both planets, but with opposite direction]
-                let force = force_dir * G * (virtual_planet_mass * *other_virtual_planet_mass) / sqr_dst;
-                // calculate the acceleration for one planet and apply it
-                let planet_acceleration = force / virtual_planet_mass;
-                virtual_planet_velocity += planet_acceleration * game.stepsize;
-
-                // calculate the acceleration for the other planet and apply it
-                let other_planet_acceleration = -force / *other_virtual_planet_mass;
-                *other_virtual_planet_velocity += other_planet_acceleration * game.stepsize;
-            }
-            // we then move the values to the bodies vector, thats why we needed to clone the virtual_planets
-            bodies.push((virtual_transform, (virtual_planet_velocity, virtual_planet_mass), virtual_planet_name))
+    if let Some(result) = prediction.task.as_mut().and_then(|task| block_on(future::poll_once(task))) {
+        prediction.result = result;
+        prediction.result_input = prediction.input.clone();
+        prediction.task = None;
+    }
+    let running = game_state.get() == &GameState::Running;
+
+    // the same NBodySystem as in update_physics [Note: the initial velocity is not yet applied to the planets, so it gets added here]
+    let mut system = new_system(&game, &force_laws);
+    let input = PredictionInput {
+        bodies: planet_q.iter().map(|(entity, planet)| PredictionBody {
+            entity,
+            position: planet.position,
+            velocity: planet.start_velocity(),
+            mass: planet.mass,
+            charge: planet.charge,
+            radius: planet.radius,
+            pinned: planet.pinned,
+        }).collect(),
+        system: PredictionSystem::new(&system),
+        stepsize: game.stepsize,
+        prediction: game.prediction,
+        frame: game.path_frame,
+    };
+    prediction.result_current = prediction.result_input.fits(&input, running);
+    // a running calculation only gets replaced (and with that cancelled, see predict_paths) if its result wouldnt fit anymore
+    let keep = if prediction.task.is_some() { prediction.input.fits(&input, running) } else { prediction.input == input };
+    if keep {
+        return;
+    }
+
+    for body in input.bodies.iter() {
+        let index = system.add_body(body.position, body.velocity, body.mass);
+        system.set_charge(index, body.charge);
+        if body.pinned {
+            system.pin(index);
         }
+    }
+    let entities = input.bodies.iter().map(|body| body.entity).collect::<Vec<_>>();
+    let radii = input.bodies.iter().map(|body| body.radius).collect::<Vec<_>>();
+    let (stepsize, settings, frame) = (input.stepsize, input.prediction, input.frame);
+    prediction.input = input;
+    // replacing the task drops the one that is still running, which cancels it (see predict_paths)
+    prediction.task = Some(AsyncComputeTaskPool::get().spawn(async move {
+        predict_paths(system, entities, &radii, stepsize, settings, frame).await
+    }));
+}
+
+// more events would only cover the paths
+const MAX_PREDICTED_EVENTS: usize = 50;
+
+// a dropped task only stops where it waits, so the prediction gives the other tasks a chance every this many steps
+const PREDICTION_YIELD_INTERVAL: u32 = 100;
 
-        _elapsed2 = _now.elapsed();
-        _now = Instant::now();
-
-        // move all calculated bodies back to virtual planets so they can get used in the next iteration
-        virtual_planets = bodies.into_iter().map(|(transform, (planet_velocity, planet_mass), planet_name)| {
-            (transform, planet_velocity, planet_mass, planet_name)
-        }).collect::<Vec<_>>();
-        // ############# Time Measure #############
-        _elapsed3 = _now.elapsed();
-        _now = Instant::now();
-
-        // go over all virtual planets and move them, then push the position to the points vector
-        for (transform, planet_velocity, _planet_mass, virtual_planet_name) in virtual_planets.iter_mut() {
-            transform.translation += *planet_velocity * game.stepsize;
-            // TODO: make this dynamic, so more than the path of two planets can be drawn
-            
-            if !points.contains_key(virtual_planet_name) {
-                points.insert(virtual_planet_name.clone(), vec![]);
+// steps a copy of the system into the future and returns the positions of every body, the center of mass at the same points in time,
+// and where the bodies collide or pass each other closely (entities and radii are in the same order as the bodies in the system)
+async fn predict_paths(
+    mut system: NBodySystem,
+    entities: Vec<Entity>,
+    radii: &[f64],
+    stepsize: f64,
+    settings: PredictionSettings,
+    frame: ReferenceFrame,
+) -> PredictedPaths {
+    let count = system.len();
+    let mut points = vec![Vec::<Vec3>::new(); count];
+    let mut barycenter = Vec::new();
+    let mut events = Vec::new();
+    let total_mass = system.masses.iter().sum::<f64>();
+    let center_of_mass = |system: &NBodySystem| {
+        let weighted = system.positions.iter().zip(system.masses.iter()).map(|(position, mass)| *position * *mass).sum::<DVec3>();
+        if total_mass > 0.0 { (weighted / total_mass).as_vec3() } else { Vec3::ZERO }
+    };
+    let frame_index = match frame {
+        ReferenceFrame::Planet(entity) => entities.iter().position(|other| *other == entity),
+        _ => None,
+    };
+    let origin = |system: &NBodySystem| match (frame, frame_index) {
+        (ReferenceFrame::Barycenter, _) => center_of_mass(system),
+        (_, Some(index)) => system.positions[index].as_vec3(),
+        _ => Vec3::ZERO,
+    };
+    // every pair closer than the close approach distance also overlaps with these radii, so only they need to be checked (see find_collisions)
+    let search_radii = radii.iter().map(|radius| radius + settings.close_approach_distance / 2.0).collect::<Vec<_>>();
+    // pairs (i, j) with i < j that already collided, and the closest point of every close approach that is going on
+    let mut collided = HashSet::new();
+    let mut closest = HashMap::<(usize, usize), PredictedEvent>::new();
+
+    let sample_interval = settings.sample_interval.max(1);
+    for step in 1..=settings.steps(stepsize) {
+        if step % PREDICTION_YIELD_INTERVAL == 0 {
+            future::yield_now().await;
+        }
+        system.step(stepsize);
+
+        // the events get checked every step, so no collision gets missed between two points of the path
+        let time = step as f64 * stepsize;
+        let mut close_pairs = HashSet::new();
+        for (i, j) in find_collisions(&system.positions, &search_radii) {
+            if collided.contains(&(i, j)) {
+                continue;
+            }
+            let distance = system.positions[i].distance(system.positions[j]);
+            let touching = distance <= radii[i] + radii[j];
+            let close = distance < settings.close_approach_distance;
+            if close {
+                close_pairs.insert((i, j));
             }
-            for (name, calculated_points) in points.iter_mut() {
-                if name == virtual_planet_name {
-                    calculated_points.push(Vec2::new(transform.translation.x, transform.translation.y));
+            // [Note: a match instead of Option::is_none_or, which needs a newer rust version]
+            let closer = match closest.get(&(i, j)) {
+                Some(event) => distance < event.distance,
+                None => true,
+            };
+            if touching || (close && closer) {
+                let event = PredictedEvent {
+                    kind: if touching { PredictedEventKind::Collision } else { PredictedEventKind::CloseApproach },
+                    bodies: (i, j),
+                    time,
+                    distance,
+                    positions: (system.positions[i].as_vec3(), system.positions[j].as_vec3()),
+                    origin: origin(&system),
+                };
+                if touching {
+                    // the close approach that led to the collision is not reported on its own
+                    collided.insert((i, j));
+                    closest.remove(&(i, j));
+                    events.push(event);
+                } else {
+                    closest.insert((i, j), event);
                 }
             }
         }
+        // the close approaches that are over, their closest point is known now
+        let ended = closest.keys().filter(|pair| !close_pairs.contains(pair)).copied().collect::<Vec<_>>();
+        for pair in ended {
+            events.extend(closest.remove(&pair));
+        }
+
+        // the calculation needs every step, but the path only every n-th point
+        if step % sample_interval != 0 {
+            continue;
+        }
+        for (calculated_points, position) in points.iter_mut().zip(system.positions.iter()) {
+            calculated_points.push(position.as_vec3());
+        }
+        barycenter.push(center_of_mass(&system));
+    }
+    // close approaches that are still going on at the end of the prediction
+    events.extend(closest.into_values());
+    // [Note: the pair breaks ties, so the order doesnt depend on the order of the hash map]
+    events.sort_by(|a, b| a.time.total_cmp(&b.time).then(a.bodies.cmp(&b.bodies)));
+    events.truncate(MAX_PREDICTED_EVENTS);
+
+    PredictedPaths { paths: entities.into_iter().zip(points).collect(), barycenter, events }
+}
+
+// reference frame the paths get drawn in (see ReferenceFrame)
+struct PathFrame<'a> {
+    // index of the planet in the prediction, None for the barycenter
+    index: Option<usize>,
+    // its predicted path and where it is now
+    path: &'a [Vec3],
+    now: Vec3,
+}
 
-        _elapsed4 = _now.elapsed();
+// None if the paths get drawn with absolute positions (or the planet of the reference frame doesnt exist anymore)
+fn path_frame<'a>(result: &'a PredictedPaths, frame: ReferenceFrame, planet_q: &Query<&Planet>) -> Option<PathFrame<'a>> {
+    match frame {
+        ReferenceFrame::Absolute => None,
+        ReferenceFrame::Barycenter => {
+            let (weighted, total_mass) = planet_q.iter().fold((DVec3::ZERO, 0.0), |(weighted, total_mass), planet| (weighted + planet.position * planet.mass, total_mass + planet.mass));
+            let now = if total_mass > 0.0 { weighted / total_mass } else { DVec3::ZERO };
+            Some(PathFrame { index: None, path: result.barycenter.as_slice(), now: now.as_vec3() })
+        }
+        ReferenceFrame::Planet(entity) => {
+            let index = result.paths.iter().position(|(other, _)| *other == entity)?;
+            let planet = planet_q.get(entity).ok()?;
+            Some(PathFrame { index: Some(index), path: result.paths[index].1.as_slice(), now: planet.position.as_vec3() })
+        }
     }
-    _now = Instant::now();
-    // finally, draw the calculated points as lines
-    for (_name, calculated_points) in points.iter() {
-        gizmos.linestrip_2d(calculated_points.clone(), Color::WHITE);
+}
+
+// draws the paths of the last finished prediction (see update_path_prediction)
+fn draw_future_planet_movement (
+    mut gizmos: Gizmos,
+    prediction: Res<PathPrediction>,
+    planet_q: Query<&Planet>,
+    game: Res<Game>
+) {
+    if !prediction.result_current {
+        return;
     }
-    let _elapsed5 = _now.elapsed();
+    let result = &prediction.result;
+    // the path of the reference frame gets subtracted from all others, and they get drawn around its current position
+    let frame = path_frame(result, game.path_frame, &planet_q);
 
-    // this is just for debugging purposes [performance measurement]
-    // println!("Transform Planets to Virtual Planets: {:?}, Calculating Physics: {:?}, Moving from bodies to Planets: {:?}, Update Position and add points: {:?}, Draw Lines: {:?}", _elapsed1, _elapsed2, _elapsed3, _elapsed4, _elapsed5);
+    // finally, draw the calculated points as lines (in 2D seen from above), in the color of the planet
+    // [Note: planets that got removed since the calculation started dont get drawn anymore]
+    for (index, (entity, calculated_points)) in result.paths.iter().enumerate() {
+        let Ok(planet) = planet_q.get(*entity) else {
+            continue;
+        };
+        // the path of the reference itself would only be a point
+        if planet.hide_path || frame.as_ref().is_some_and(|frame| frame.index == Some(index)) {
+            continue;
+        }
+        let points = match &frame {
+            Some(frame) => calculated_points.iter().zip(frame.path.iter()).map(|(point, frame_point)| *point - *frame_point + frame.now).collect::<Vec<_>>(),
+            None => calculated_points.clone(),
+        };
+        match game.view_mode {
+            ViewMode::TwoD => gizmos.linestrip_2d(points.into_iter().map(|point| point.truncate()), planet.color),
+            ViewMode::ThreeD => gizmos.linestrip(points, planet.color),
+        }
+    }
+}
+
+const COLLISION_COLOR: Color = Color::RED;
+const CLOSE_APPROACH_COLOR: Color = Color::YELLOW;
+
+// marks the collisions and close approaches of the last prediction on the paths: both planets at that moment, connected by a line,
+// and a label with the time until it happens (and the distance)
+fn draw_predicted_events(
+    mut gizmos: Gizmos,
+    prediction: Res<PathPrediction>,
+    planet_q: Query<&Planet>,
+    game: Res<Game>,
+    camera_q: Query<(&Camera, &GlobalTransform)>,
+    mut egui_ctx: Query<&mut EguiContext, With<PrimaryWindow>>
+) {
+    if !prediction.result_current {
+        return;
+    }
+    let result = &prediction.result;
+    let frame = path_frame(result, game.path_frame, &planet_q);
+    let camera = camera_q.iter().find(|(camera, _)| camera.is_active);
+    let Ok(mut egui_ctx) = egui_ctx.get_single_mut() else {
+        return;
+    };
+    // the labels are behind the UI panels
+    let painter = egui_ctx.get_mut().layer_painter(egui::LayerId::new(egui::Order::Background, egui::Id::new("predicted_events")));
+
+    for event in result.events.iter() {
+        let (Some((entity_a, _)), Some((entity_b, _))) = (result.paths.get(event.bodies.0), result.paths.get(event.bodies.1)) else {
+            continue;
+        };
+        let (Ok(planet_a), Ok(planet_b)) = (planet_q.get(*entity_a), planet_q.get(*entity_b)) else {
+            continue;
+        };
+        // only events where at least one path is shown
+        if planet_a.hide_path && planet_b.hide_path {
+            continue;
+        }
+        // same shift into the reference frame as for the paths
+        let offset = frame.as_ref().map_or(Vec3::ZERO, |frame| frame.now - event.origin);
+        let (a, b) = (event.positions.0 + offset, event.positions.1 + offset);
+        let (color, text) = match event.kind {
+            PredictedEventKind::Collision => (COLLISION_COLOR, format!("Collision: {} in {}", game.units.format(event.distance, Dimension::LENGTH), game.units.format(event.time, Dimension::TIME))),
+            PredictedEventKind::CloseApproach => (CLOSE_APPROACH_COLOR, format!("{} in {}", game.units.format(event.distance, Dimension::LENGTH), game.units.format(event.time, Dimension::TIME))),
+        };
+
+        let label_position = match game.view_mode {
+            ViewMode::TwoD => {
+                gizmos.line_2d(a.truncate(), b.truncate(), color);
+                gizmos.circle_2d(a.truncate(), planet_a.radius as f32, color);
+                gizmos.circle_2d(b.truncate(), planet_b.radius as f32, color);
+                ((a + b) / 2.0).truncate().extend(0.0)
+            }
+            ViewMode::ThreeD => {
+                gizmos.line(a, b, color);
+                gizmos.sphere(a, Quat::IDENTITY, planet_a.radius as f32, color);
+                gizmos.sphere(b, Quat::IDENTITY, planet_b.radius as f32, color);
+                (a + b) / 2.0
+            }
+        };
+        if let Some(screen_position) = camera.and_then(|(camera, global_transform)| camera.world_to_viewport(global_transform, label_position)) {
+            let [r, g, b, _] = color.as_rgba_u8();
+            painter.text(egui::pos2(screen_position.x, screen_position.y), egui::Align2::CENTER_BOTTOM, text, egui::FontId::proportional(13.0), egui::Color32::from_rgb(r, g, b));
+        }
+    }
 }
 
 // ############# OnEnter Systems: GameState::Running #############
@@ -319,11 +934,10 @@ fn set_initial_velocity(
     mut query: Query<&mut Planet>
 ) {
     for mut planet in query.iter_mut() {
-        // add initial velocity to current velocity
-        let velocity = planet.initial_velocity;
-        planet.velocity += velocity;
+        // add initial velocity to current velocity (pinned planets dont move at all)
+        planet.velocity = planet.start_velocity();
         // set initial velocity to zero, so it doesnt get added the next time we pause and play the simulation
-        planet.initial_velocity = Vec3::ZERO;
+        planet.initial_velocity = DVec3::ZERO;
     }
 }
 
@@ -340,23 +954,71 @@ fn clicked_on_planet(
     }
 }
 
+// position, velocity and mass of what a planet orbits (None if there is nothing to orbit)
+// the planet itself (if it is one of the planets) is never part of its primary
+// [Note: the initial velocity is not yet applied to the planets, so the start velocity gets used (same as in draw_future_planet_movement)]
+pub(crate) fn orbit_primary(planets: &[(Entity, &Planet)], primary: OrbitPrimary, planet: Option<Entity>) -> Option<(DVec3, DVec3, f64)> {
+    if let OrbitPrimary::Planet(primary) = primary {
+        if let Some((entity, primary)) = planets.iter().find(|(entity, _)| *entity == primary) {
+            return if Some(*entity) == planet { None } else { Some((primary.position, primary.start_velocity(), primary.mass)) };
+        }
+    }
+    // barycenter: center of mass of all (other) planets, moving with their total momentum
+    let (mut mass, mut weighted_position, mut momentum) = (0.0, DVec3::ZERO, DVec3::ZERO);
+    for (_, other) in planets.iter().filter(|(entity, _)| Some(*entity) != planet) {
+        mass += other.mass;
+        weighted_position += other.position * other.mass;
+        momentum += other.start_velocity() * other.mass;
+    }
+    if mass > 0.0 { Some((weighted_position / mass, momentum / mass, mass)) } else { None }
+}
+
+// osculating orbital elements of a planet relative to its primary, and G * (mass of the primary + mass of the planet)
+// (None for the primary itself)
+pub(crate) fn osculating_elements(planets: &[(Entity, &Planet)], entity: Entity, primary: OrbitPrimary, gravitational_constant: f64) -> Option<(OrbitalElements, f64)> {
+    let (_, planet) = planets.iter().find(|(other, _)| *other == entity)?;
+    let (primary_position, primary_velocity, primary_mass) = orbit_primary(planets, primary, Some(entity))?;
+    let gravitational_parameter = gravitational_constant * (primary_mass + planet.mass);
+    let elements = state_to_elements(planet.position - primary_position, planet.start_velocity() - primary_velocity, gravitational_parameter);
+    Some((elements, gravitational_parameter))
+}
+
 fn spawn_new_planets (
     buttons: Res<ButtonInput<MouseButton>>,
-    commands: Commands,
+    mut commands: Commands,
     mut game: ResMut<Game>,
     asset_server: Res<AssetServer>,
     window_q: Query<&Window, With<PrimaryWindow>>,
-    camera_q: Query<(&Camera, &GlobalTransform), With<MyCamera>>
+    camera_q: Query<(&Camera, &GlobalTransform, Has<OrbitCamera>)>,
+    planet_q: Query<(Entity, &Planet)>
 ) {
     if buttons.just_pressed(MouseButton::Right) {
         let window = window_q.get_single().unwrap();
         // fetch window to get mouse position
         if let Some(window_position) = window.cursor_position() {
-            // fetch camera and its global transform
-            let (camera, global_transform) = camera_q.single();
+            // fetch the active camera (2D or 3D) and its global transform
+            let Some((camera, global_transform, is_3d)) = camera_q.iter().find(|(camera, ..)| camera.is_active) else {
+                return;
+            };
             // convert window position to world position
-            if let Some(cursor_position) = camera.viewport_to_world_2d(global_transform, window_position) {
-                spawn_planet(commands, asset_server.as_ref(), game.as_mut(), Vec3::new(cursor_position.x, cursor_position.y, 0.))
+            // [Note: in 3D the planet gets placed where the mouse points at the xy plane]
+            let cursor_position = if is_3d {
+                camera.viewport_to_world(global_transform, window_position)
+                    .and_then(|ray| ray.intersect_plane(Vec3::ZERO, Plane3d::new(Vec3::Z)).map(|distance| ray.get_point(distance)))
+            } else {
+                camera.viewport_to_world_2d(global_transform, window_position).map(|position| position.extend(0.0))
+            };
+            if let Some(cursor_position) = cursor_position {
+                let mut planet = default_planet(&game, cursor_position.as_dvec3());
+                if game.spawn_mode == SpawnMode::Orbit {
+                    if let Some((primary_position, primary_velocity, primary_mass)) = orbit_primary(&planet_q.iter().collect::<Vec<_>>(), game.orbit_primary, None) {
+                        planet.velocity = orbit_velocity(
+                            planet.position, primary_position, primary_velocity, primary_mass, planet.mass,
+                            game.units.system.gravitational_constant(), game.orbit_shape
+                        );
+                    }
+                }
+                spawn_planet(&mut commands, asset_server.as_ref(), game.as_mut(), planet, None);
             }
         }
     }
@@ -0,0 +1,338 @@
+use std::{collections::HashMap, fs};
+
+use bevy::{math::DVec3, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{particles::{Body, TestParticle}, physics::BodyInputs, planets::{spawn_planet_entity, AdaptiveStep, ForceLaws, Planet, PlanetLabel, PLANET_TEXTURE}, world::{Game, GameState, OrbitPrimary, ReferenceFrame}};
+
+pub struct ScenarioPlugin;
+
+impl Plugin for ScenarioPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        // file name and the save / load buttons of the UI
+        .init_resource::<ScenarioFile>()
+        .add_systems(Update, (save_scenario, load_scenario))
+        ;
+    }
+}
+
+// ############# Scenario File Resource #############
+// the UI sets request, the systems below do the work and write what happened into message
+#[derive(Resource)]
+pub struct ScenarioFile {
+    // relative to the working directory
+    pub path: String,
+    pub request: Option<ScenarioRequest>,
+    pub message: String,
+}
+
+impl Default for ScenarioFile {
+    fn default() -> Self {
+        Self { path: "scenario.ron".to_string(), request: None, message: String::new() }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScenarioRequest {
+    Save,
+    Load,
+}
+
+// ############# Scenario #############
+// version of the file format, files with a newer version get rejected
+// [Note: increase it whenever a field gets added, removed or changes its meaning]
+pub const SCENARIO_VERSION: u32 = 1;
+
+// everything that is needed to restore a simulation exactly: settings, planets and test particles
+#[derive(Serialize, Deserialize)]
+pub struct Scenario {
+    pub version: u32,
+    pub game: Game,
+    // sub step size of adaptive integrators (see AdaptiveStep), the simulation continues exactly where it was
+    pub adaptive_step: f64,
+    pub force_law: SavedForceLaw,
+    pub planets: Vec<SavedPlanet>,
+    pub particles: Vec<SavedParticle>,
+}
+
+// only the version, so a file of a newer version gets a clear error instead of a parse error somewhere in the middle
+#[derive(Deserialize)]
+struct ScenarioVersion {
+    version: u32,
+}
+
+impl Scenario {
+    pub fn to_ron(&self) -> Result<String, String> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).map_err(|error| error.to_string())
+    }
+
+    pub fn from_ron(text: &str) -> Result<Self, String> {
+        let version = ron::from_str::<ScenarioVersion>(text).map_err(|error| error.to_string())?.version;
+        if version > SCENARIO_VERSION {
+            return Err(format!("version {version} is newer than {SCENARIO_VERSION}"));
+        }
+        ron::from_str(text).map_err(|error| error.to_string())
+    }
+}
+
+// the force law gets found again by its name (it has to be registered in the ForceLaws resource)
+#[derive(Serialize, Deserialize)]
+pub struct SavedForceLaw {
+    pub name: String,
+    // name and value of every parameter (see ForceLaw::parameters)
+    pub parameters: Vec<(String, f64)>,
+}
+
+// all fields of the Planet, and what else the planet entity is made of
+// [Note: the vectors are stored as arrays, glam (DVec3, Vec3, Quat) can only be serialized with the serialize feature of bevy]
+#[derive(Serialize, Deserialize)]
+pub struct SavedPlanet {
+    // the entity when it was saved, only used to find the planets the game settings refer to (e.g. the orbit primary)
+    pub entity: Entity,
+    pub name: String,
+    // the number that gets shown on the planet
+    pub label: String,
+    pub texture: String,
+    pub translation: [f32; 3],
+    pub rotation: [f32; 4],
+    pub scale: [f32; 3],
+    pub mass: f64,
+    pub position: [f64; 3],
+    pub velocity: [f64; 3],
+    pub acceleration: [f64; 3],
+    pub radius: f64,
+    pub initial_velocity: [f64; 3],
+    pub inputs: BodyInputs,
+    pub pinned: bool,
+    pub charge: f64,
+    pub color: Color,
+    pub hide_path: bool,
+}
+
+impl SavedPlanet {
+    fn new(entity: Entity, planet: &Planet, transform: &Transform, name: String, label: String, texture: String) -> Self {
+        Self {
+            entity,
+            name,
+            label,
+            texture,
+            translation: transform.translation.to_array(),
+            rotation: transform.rotation.to_array(),
+            scale: transform.scale.to_array(),
+            mass: planet.mass,
+            position: planet.position.to_array(),
+            velocity: planet.velocity.to_array(),
+            acceleration: planet.acceleration.to_array(),
+            radius: planet.radius,
+            initial_velocity: planet.initial_velocity.to_array(),
+            inputs: planet.inputs,
+            pinned: planet.pinned,
+            charge: planet.charge,
+            color: planet.color,
+            hide_path: planet.hide_path,
+        }
+    }
+
+    fn planet(&self) -> Planet {
+        Planet {
+            mass: self.mass,
+            position: DVec3::from_array(self.position),
+            velocity: DVec3::from_array(self.velocity),
+            acceleration: DVec3::from_array(self.acceleration),
+            radius: self.radius,
+            initial_velocity: DVec3::from_array(self.initial_velocity),
+            inputs: self.inputs,
+            pinned: self.pinned,
+            charge: self.charge,
+            color: self.color,
+            hide_path: self.hide_path,
+        }
+    }
+
+    fn transform(&self) -> Transform {
+        Transform {
+            translation: Vec3::from_array(self.translation),
+            rotation: Quat::from_array(self.rotation),
+            scale: Vec3::from_array(self.scale),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedParticle {
+    pub position: [f64; 3],
+    pub velocity: [f64; 3],
+}
+
+// ############# Systems #############
+// everything a planet entity is made of
+type PlanetEntity<'a> = (Entity, &'a Planet, &'a Transform, &'a Name, &'a Handle<Image>, &'a Children);
+
+#[allow(clippy::too_many_arguments)]
+fn save_scenario(
+    mut scenario_file: ResMut<ScenarioFile>,
+    planet_q: Query<PlanetEntity>,
+    label_q: Query<&Text, With<PlanetLabel>>,
+    particle_q: Query<&TestParticle>,
+    game: Res<Game>,
+    adaptive_step: Res<AdaptiveStep>,
+    force_laws: Res<ForceLaws>,
+    asset_server: Res<AssetServer>,
+) {
+    if scenario_file.request != Some(ScenarioRequest::Save) {
+        return;
+    }
+    scenario_file.request = None;
+
+    let force_law = force_laws.active();
+    let force_law = SavedForceLaw {
+        name: force_law.name().to_string(),
+        parameters: force_law.parameters().into_iter().map(|(name, value, _)| (name.to_string(), value)).collect(),
+    };
+    let mut planets = planet_q.iter().map(|(entity, planet, transform, name, texture, children)| {
+        let label = children.iter()
+            .find_map(|child| label_q.get(*child).ok())
+            .and_then(|text| text.sections.first())
+            .map(|section| section.value.clone())
+            .unwrap_or_default();
+        let texture = asset_server.get_path(texture.id()).map_or(PLANET_TEXTURE.to_string(), |path| path.to_string());
+        SavedPlanet::new(entity, planet, transform, name.to_string(), label, texture)
+    }).collect::<Vec<_>>();
+    // the query has no fixed order, the entities are (mostly) in the order the planets got spawned
+    planets.sort_by_key(|planet| planet.entity);
+    let particles = particle_q.iter()
+        .map(|particle| SavedParticle { position: particle.position.to_array(), velocity: particle.velocity.to_array() })
+        .collect();
+    let scenario = Scenario { version: SCENARIO_VERSION, game: game.clone(), adaptive_step: adaptive_step.step_size, force_law, planets, particles };
+
+    let path = scenario_file.path.clone();
+    scenario_file.message = match scenario.to_ron().and_then(|text| fs::write(&path, text).map_err(|error| error.to_string())) {
+        Ok(()) => format!("Saved {} planets to {path}", scenario.planets.len()),
+        Err(error) => format!("Could not save {path}: {error}"),
+    };
+}
+
+// replaces all planets and particles (and the settings) with the ones from the file
+#[allow(clippy::too_many_arguments)]
+fn load_scenario(
+    mut commands: Commands,
+    mut scenario_file: ResMut<ScenarioFile>,
+    body_q: Query<Entity, Body>,
+    mut game: ResMut<Game>,
+    mut adaptive_step: ResMut<AdaptiveStep>,
+    mut force_laws: ResMut<ForceLaws>,
+    asset_server: Res<AssetServer>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if scenario_file.request != Some(ScenarioRequest::Load) {
+        return;
+    }
+    scenario_file.request = None;
+
+    let path = scenario_file.path.clone();
+    // nothing gets despawned if the file can not be read
+    let scenario = match fs::read_to_string(&path).map_err(|error| error.to_string()).and_then(|text| Scenario::from_ron(&text)) {
+        Ok(scenario) => scenario,
+        Err(error) => {
+            scenario_file.message = format!("Could not load {path}: {error}");
+            return;
+        }
+    };
+
+    for entity in body_q.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    // old entity -> new entity
+    let entities = scenario.planets.iter().map(|saved| {
+        let entity = spawn_planet_entity(
+            &mut commands, asset_server.as_ref(), saved.planet(), saved.name.clone(), saved.label.clone(), &saved.texture, saved.transform()
+        );
+        (saved.entity, entity)
+    }).collect::<HashMap<_, _>>();
+    commands.spawn_batch(scenario.particles.iter().map(|particle| TestParticle {
+        position: DVec3::from_array(particle.position),
+        velocity: DVec3::from_array(particle.velocity),
+    }).collect::<Vec<_>>());
+
+    *game = scenario.game;
+    // the rejected steps count from the start of the loaded simulation
+    *adaptive_step = AdaptiveStep { step_size: scenario.adaptive_step, rejected_steps: 0 };
+    game.orbit_primary = map_primary(game.orbit_primary, &entities);
+    game.elements_primary = map_primary(game.elements_primary, &entities);
+    game.belt_primary = map_primary(game.belt_primary, &entities);
+    if let ReferenceFrame::Planet(entity) = game.path_frame {
+        game.path_frame = entities.get(&entity).map_or(ReferenceFrame::Absolute, |entity| ReferenceFrame::Planet(*entity));
+    }
+
+    let mut message = format!("Loaded {} planets from {path}", scenario.planets.len());
+    match force_laws.names().into_iter().find(|(_, name)| *name == scenario.force_law.name) {
+        Some((index, _)) => {
+            force_laws.select(index);
+            for (name, value) in scenario.force_law.parameters.iter() {
+                force_laws.active_mut().set_parameter(name, *value);
+            }
+        }
+        None => message += &format!(" (unknown force law {}, kept the current one)", scenario.force_law.name),
+    }
+    scenario_file.message = message;
+
+    // like after creating a system in the editor: the user starts it
+    next_state.set(GameState::Paused);
+}
+
+// planets that are not in the file (can only happen if it was edited by hand) fall back to the barycenter
+fn map_primary(primary: OrbitPrimary, entities: &HashMap<Entity, Entity>) -> OrbitPrimary {
+    match primary {
+        OrbitPrimary::Planet(entity) => entities.get(&entity).map_or(OrbitPrimary::Barycenter, |entity| OrbitPrimary::Planet(*entity)),
+        primary => primary,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scenario(version: u32) -> Scenario {
+        Scenario {
+            version,
+            game: Game::default(),
+            adaptive_step: 0.1 / 3.0,
+            force_law: SavedForceLaw { name: "Yukawa".to_string(), parameters: vec![("Range".to_string(), 1234.5)] },
+            planets: vec![SavedPlanet::new(
+                Entity::from_raw(7),
+                &Planet { mass: 5.972e24, position: DVec3::new(0.1, -2.0 / 3.0, 1e-300), velocity: DVec3::new(1.0 / 7.0, 0.0, -3.5), ..default() },
+                &Transform::from_xyz(0.1, 0.2, 0.3),
+                "Earth".to_string(),
+                "1".to_string(),
+                PLANET_TEXTURE.to_string(),
+            )],
+            particles: vec![SavedParticle { position: [1.0, 2.0, 3.0], velocity: [f64::MIN_POSITIVE, -0.0, 1e20] }],
+        }
+    }
+
+    #[test]
+    fn ron_round_trip() {
+        let text = scenario(SCENARIO_VERSION).to_ron().unwrap();
+        let loaded = Scenario::from_ron(&text).unwrap();
+        // the values have to come back exactly, otherwise the loaded simulation would run differently
+        let planet = loaded.planets[0].planet();
+        assert_eq!(planet.position, DVec3::new(0.1, -2.0 / 3.0, 1e-300));
+        assert_eq!(planet.velocity, DVec3::new(1.0 / 7.0, 0.0, -3.5));
+        assert_eq!(planet.mass, 5.972e24);
+        assert_eq!(loaded.planets[0].entity, Entity::from_raw(7));
+        assert_eq!(loaded.adaptive_step, 0.1 / 3.0);
+        assert_eq!(loaded.force_law.parameters, vec![("Range".to_string(), 1234.5)]);
+        assert_eq!(loaded.particles[0].velocity, [f64::MIN_POSITIVE, -0.0, 1e20]);
+        // and everything else too
+        assert_eq!(loaded.to_ron().unwrap(), text);
+    }
+
+    #[test]
+    fn newer_version_gets_rejected() {
+        let text = scenario(SCENARIO_VERSION + 1).to_ron().unwrap();
+        let error = Scenario::from_ron(&text).err().unwrap();
+        assert!(error.contains("newer"), "{error}");
+        assert!(Scenario::from_ron(&scenario(SCENARIO_VERSION).to_ron().unwrap()).is_ok());
+    }
+}
@@ -1,13 +1,23 @@
+use std::hash::Hash;
+
+use bevy::utils::HashMap;
+
 use bevy::{prelude::*, window::PrimaryWindow};
 
 use bevy_egui::egui::{DragValue, RichText};
 use bevy_inspector_egui::{bevy_egui::{egui, EguiContext, EguiPlugin}, bevy_inspector::{self, hierarchy::SelectedEntities}, DefaultInspectorConfigPlugin};
 
-use crate::{planets::Planet, world::{Game, GameState}};
+use crate::{diagnostics::ConservationDiagnostics, editor::SystemEditor, physics::{BodyInputs, BodyProperties, CollisionMode, Dimension, ForceSolver, Integrator, LengthUnit, MassUnit, OrbitDirection, TimeUnit, UnitSystem, Units, C}, particles::{spawn_belt, TestParticle}, planets::{orbit_primary, osculating_elements, AdaptiveStep, ForceLaws, Planet, MAX_PREDICTION_STEPS}, scenario::{ScenarioFile, ScenarioRequest}, world::{Game, GameState, OrbitPrimary, ReferenceFrame, SpawnMode, ViewMode}};
 
 pub struct UiPlugin;
 
-const ACCENT_COLOR: egui::Color32 = egui::Color32::from_rgb(0, 255, 0);
+pub(crate) const ACCENT_COLOR: egui::Color32 = egui::Color32::from_rgb(0, 255, 0);
+
+// bevy color as egui color
+fn egui_color(color: Color) -> egui::Color32 {
+    let [r, g, b, _] = color.as_rgba_u8();
+    egui::Color32::from_rgb(r, g, b)
+}
 
 impl Plugin for UiPlugin {
     fn build(&self, app: &mut App) {
@@ -65,86 +75,522 @@ fn _inspector_ui(world: &mut World, mut selected_entities: Local<SelectedEntitie
         });
 }
 
+// DragValue that shows the value in the display units; what the user enters gets converted back to simulation units
+pub(crate) fn unit_drag_value(ui: &mut egui::Ui, enabled: bool, value: &mut f64, dimension: Dimension, units: &Units, prefix: &str) -> bool {
+    let mut displayed = units.to_display(*value, dimension);
+    let speed = (displayed.abs() * 0.01).max(0.01);
+    let changed = ui.add_enabled(
+        enabled,
+        DragValue::new(&mut displayed)
+        .speed(speed)
+        .custom_formatter(|value, _| {
+            // very big and very small values are unreadable without exponent
+            if value != 0.0 && !(1e-3..1e6).contains(&value.abs()) { format!("{:.4e}", value) } else { format!("{:.3}", value) }
+        })
+        .prefix(prefix)
+        .suffix(format!(" {}", units.display.symbol(dimension)))
+    ).changed();
+    if changed {
+        *value = units.to_simulation(displayed, dimension);
+    }
+    changed
+}
+
+// combo box for which two properties are given, the two given properties are editable and the other two only get displayed
+// returns the new properties if they were changed
+pub(crate) fn body_properties_ui(ui: &mut egui::Ui, id_source: impl Hash, inputs: &mut BodyInputs, properties: BodyProperties, units: &Units) -> Option<BodyProperties> {
+    ui.horizontal(|ui| {
+        ui.label("Given: ");
+        egui::ComboBox::from_id_source(id_source)
+            .selected_text(inputs.name())
+            .show_ui(ui, |ui| {
+                for body_inputs in BodyInputs::ALL {
+                    ui.selectable_value(inputs, body_inputs, body_inputs.name());
+                }
+            });
+    });
+    let (first, second) = properties.inputs(*inputs);
+    let mut values = [first, second];
+    let (mass_given, radius_given, density_given, gravity_given) = inputs.is_input();
+    let mut next_input = 0;
+    let mut changed = false;
+    for (label, given, mut value, dimension) in [
+        ("Mass: ", mass_given, properties.mass, Dimension::MASS),
+        ("Radius: ", radius_given, properties.radius, Dimension::LENGTH),
+        ("Density: ", density_given, properties.density, Dimension::DENSITY),
+        ("Surface Gravity: ", gravity_given, properties.surface_gravity, Dimension::ACCELERATION),
+    ] {
+        // the inputs are in the same order as the properties are listed here
+        let edited = if given { next_input += 1; &mut values[next_input - 1] } else { &mut value };
+        ui.horizontal(|ui| {
+            ui.label(label);
+            changed |= unit_drag_value(ui, given, edited, dimension, units, "");
+        });
+    }
+    if changed {
+        Some(BodyProperties::from_inputs(*inputs, values[0].max(0.0), values[1].max(0.0), units.system.gravitational_constant()))
+    } else {
+        None
+    }
+}
+
+// combo box to choose the barycenter or one of the planets
+fn orbit_primary_combo(ui: &mut egui::Ui, id_source: &str, primary: &mut OrbitPrimary, planet_names: &[(Entity, String)]) {
+    let selected = match primary {
+        OrbitPrimary::Planet(entity) => planet_names.iter().find(|(other, _)| other == entity).map(|(_, name)| name.as_str()),
+        OrbitPrimary::Barycenter => None,
+    };
+    egui::ComboBox::from_id_source(id_source)
+        .selected_text(selected.unwrap_or("Barycenter"))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(primary, OrbitPrimary::Barycenter, "Barycenter");
+            for (entity, name) in planet_names.iter() {
+                ui.selectable_value(primary, OrbitPrimary::Planet(*entity), name.as_str());
+            }
+        });
+}
+
+// combo box to choose what the predicted paths are relative to
+fn reference_frame_combo(ui: &mut egui::Ui, id_source: &str, frame: &mut ReferenceFrame, planet_names: &[(Entity, String)]) {
+    let selected = match frame {
+        ReferenceFrame::Planet(entity) => planet_names.iter().find(|(other, _)| other == entity).map(|(_, name)| name.as_str()),
+        ReferenceFrame::Barycenter => Some("Barycenter"),
+        ReferenceFrame::Absolute => None,
+    };
+    egui::ComboBox::from_id_source(id_source)
+        .selected_text(selected.unwrap_or("Absolute"))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(frame, ReferenceFrame::Absolute, "Absolute");
+            ui.selectable_value(frame, ReferenceFrame::Barycenter, "Barycenter");
+            for (entity, name) in planet_names.iter() {
+                ui.selectable_value(frame, ReferenceFrame::Planet(*entity), name.as_str());
+            }
+        });
+}
+
+// [Note: bevy systems get their data as parameters, so the argument count is not a problem here]
+#[allow(clippy::too_many_arguments)]
 fn ui_first_window_system(
-    mut planet_q: Query<(&mut Planet, &Name)>,
+    mut planet_q: Query<(Entity, &mut Planet, &Name)>,
     mut egui_ctx: Query<&mut EguiContext, With<PrimaryWindow>>,
     mut game: ResMut<Game>,
     game_state: Res<State<GameState>>,
+    adaptive_step: Res<AdaptiveStep>,
+    diagnostics: Res<ConservationDiagnostics>,
+    mut editor: ResMut<SystemEditor>,
+    mut commands: Commands,
+    particle_q: Query<Entity, With<TestParticle>>,
+    mut force_laws: ResMut<ForceLaws>,
+    mut scenario_file: ResMut<ScenarioFile>,
 ) {
+    // every value gets shown in the display units (see unit_drag_value)
+    let units = game.units;
+    let gravitational_constant = units.system.gravitational_constant();
+    // the z components only get shown in the 3D view (the 2D view is the top-down projection)
+    let is_3d = game.view_mode == ViewMode::ThreeD;
+    let uses_charge = force_laws.active().uses_charge();
+    let planet_names = planet_q.iter().map(|(entity, _, name)| (entity, name.to_string())).collect::<Vec<_>>();
+    // the elements need all planets at once, so they get calculated before the planets are edited
+    let elements = {
+        let planets = planet_q.iter().map(|(entity, planet, _)| (entity, planet)).collect::<Vec<_>>();
+        planets.iter()
+            .map(|(entity, _)| (*entity, osculating_elements(&planets, *entity, game.elements_primary, gravitational_constant)))
+            .collect::<HashMap<_, _>>()
+    };
+
     egui::SidePanel::left("Settings")
         .default_width(250.0)
         .show(egui_ctx.single_mut().get_mut(), |ui| {
-            ui.vertical(|ui| {
-                ui.separator();
-                for (mut planet, name) in planet_q.iter_mut() {
-                    ui.vertical(|ui| {
-                        ui.label(
-                            RichText::new(name.to_string()).color(ACCENT_COLOR)
-                        );
-                        ui.horizontal(|ui| {
-                            ui.label("Add Velocity: ");
-                            ui.add_enabled(
-                                if game_state.get() == &GameState::Running {false} else {true},
-                                DragValue::new(&mut planet.initial_velocity.x)
-                                .speed(0.05)
-                                .prefix("x:")
-                            );
-                            ui.add_enabled(
-                                if game_state.get() == &GameState::Running {false} else {true},
-                                DragValue::new(&mut planet.initial_velocity.y)
-                                .speed(0.05)
-                                .prefix("y:")
+            // with many planets the panel gets longer than the window
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Orbital Elements relative to: ");
+                    orbit_primary_combo(ui, "elements_primary", &mut game.elements_primary, &planet_names);
+                });
+                ui.vertical(|ui| {
+                    ui.separator();
+                    for (entity, mut planet, name) in planet_q.iter_mut() {
+                        ui.vertical(|ui| {
+                            ui.label(
+                                // same color as the label and the path of the planet
+                                RichText::new(name.to_string()).color(egui_color(planet.color))
                             );
+                            ui.horizontal(|ui| {
+                                ui.label("Position: ");
+                                let editable = game_state.get() != &GameState::Running;
+                                unit_drag_value(ui, editable, &mut planet.position.x, Dimension::LENGTH, &units, "x:");
+                                unit_drag_value(ui, editable, &mut planet.position.y, Dimension::LENGTH, &units, "y:");
+                                if is_3d {
+                                    unit_drag_value(ui, editable, &mut planet.position.z, Dimension::LENGTH, &units, "z:");
+                                }
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Add Velocity: ");
+                                let editable = game_state.get() != &GameState::Running && !planet.pinned;
+                                unit_drag_value(ui, editable, &mut planet.initial_velocity.x, Dimension::VELOCITY, &units, "x:");
+                                unit_drag_value(ui, editable, &mut planet.initial_velocity.y, Dimension::VELOCITY, &units, "y:");
+                                if is_3d {
+                                    unit_drag_value(ui, editable, &mut planet.initial_velocity.z, Dimension::VELOCITY, &units, "z:");
+                                }
+                            });
+                            // pinned planets stay where they are, but still attract the others
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut planet.pinned, "Pinned");
+                                let mut show_path = !planet.hide_path;
+                                if ui.checkbox(&mut show_path, "Show Path").changed() {
+                                    planet.hide_path = !show_path;
+                                }
+                            });
+                            if uses_charge {
+                                ui.horizontal(|ui| {
+                                    ui.label("Charge: ");
+                                    unit_drag_value(ui, true, &mut planet.charge, Dimension::CHARGE, &units, "");
+                                });
+                            }
+                            let properties = planet.properties(gravitational_constant);
+                            if let Some(properties) = body_properties_ui(ui, ("inputs", name.as_str()), &mut planet.inputs, properties, &units) {
+                                planet.mass = properties.mass;
+                                planet.radius = properties.radius;
+                            }
+                            ui.add_space(10.0);
+                            ui.horizontal(|ui| {
+                                ui.label("Current Velocity:");
+                                unit_drag_value(ui, false, &mut planet.velocity.x, Dimension::VELOCITY, &units, "x:");
+                                unit_drag_value(ui, false, &mut planet.velocity.y, Dimension::VELOCITY, &units, "y:");
+                                if is_3d {
+                                    unit_drag_value(ui, false, &mut planet.velocity.z, Dimension::VELOCITY, &units, "z:");
+                                }
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Current Acceleration: ");
+                                unit_drag_value(ui, false, &mut planet.acceleration.x, Dimension::ACCELERATION, &units, "x:");
+                                unit_drag_value(ui, false, &mut planet.acceleration.y, Dimension::ACCELERATION, &units, "y:");
+                                if is_3d {
+                                    unit_drag_value(ui, false, &mut planet.acceleration.z, Dimension::ACCELERATION, &units, "z:");
+                                }
+                            });
+                            egui::CollapsingHeader::new("Orbital Elements")
+                                .id_source(("elements", name.as_str()))
+                                .show(ui, |ui| {
+                                    match elements.get(&entity).copied().flatten() {
+                                        Some((elements, gravitational_parameter)) => {
+                                            ui.label(format!("Semi-major Axis: {}", units.format(elements.semi_major_axis, Dimension::LENGTH)));
+                                            ui.label(format!("Eccentricity: {:.4}", elements.eccentricity));
+                                            ui.label(format!("Inclination: {:.2}°", elements.inclination.to_degrees()));
+                                            ui.label(format!("Longitude of Ascending Node: {:.2}°", elements.longitude_of_ascending_node.to_degrees()));
+                                            ui.label(format!("Argument of Periapsis: {:.2}°", elements.argument_of_periapsis.to_degrees()));
+                                            ui.label(format!("Mean Anomaly: {:.2}°", elements.mean_anomaly.to_degrees()));
+                                            let period = elements.period(gravitational_parameter);
+                                            ui.label(if period.is_finite() { format!("Period: {}", units.format(period, Dimension::TIME)) } else { String::from("Period: - (not bound)") });
+                                        }
+                                        None => {
+                                            ui.label("- (this is the primary)");
+                                        }
+                                    }
+                                });
+                            ui.separator();
                         });
-                        ui.horizontal( |ui| {
-                            ui.label("Gravity: ");
-                            ui.add(
-                                DragValue::new(&mut planet.gravity)
-                                .clamp_range(0.0..=100.0)
-                            );
+                    }    
+                });
+                
+                ui.horizontal(|ui| {
+                    ui.label("Game Speed: ");
+                    ui.add_enabled(
+                        if game_state.get() == &GameState::Running {true} else {true},
+                        DragValue::new(&mut game.speed)
+                        .clamp_range(30.0..=1000.0)
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.label("View: ");
+                    egui::ComboBox::from_id_source("view_mode")
+                        .selected_text(game.view_mode.name())
+                        .show_ui(ui, |ui| {
+                            for view_mode in ViewMode::ALL {
+                                ui.selectable_value(&mut game.view_mode, view_mode, view_mode.name());
+                            }
                         });
-                        ui.horizontal(|ui| {
-                            ui.label("Radius: ");
-                            ui.add(
-                                DragValue::new(&mut planet.radius)
-                                .clamp_range(0.0..=1000.0)
-                            );
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Integrator: ");
+                    egui::ComboBox::from_id_source("integrator")
+                        .selected_text(game.integrator.name())
+                        .show_ui(ui, |ui| {
+                            for integrator in Integrator::ALL {
+                                ui.selectable_value(&mut game.integrator, integrator, integrator.name());
+                            }
                         });
-                        ui.add_space(10.0);
-                        ui.horizontal(|ui| {
-                            ui.label("Current Velocity:");
-                            ui.add_enabled(false,
-                                DragValue::new(&mut planet.velocity.x)
-                                .prefix("x:")
-                            );
-                            ui.add_enabled(false,
-                                DragValue::new(&mut planet.velocity.y)
-                                .prefix("y:")
-                            );
+                });
+                if game.integrator.is_adaptive() {
+                    ui.horizontal(|ui| {
+                        ui.label("Tolerance: ");
+                        ui.add(
+                            DragValue::new(&mut game.tolerance)
+                            .speed(1e-6)
+                            .clamp_range(1e-6..=1e-1)
+                        );
+                    });
+                    ui.label(format!("Step size: {}, rejected steps: {}", units.format(adaptive_step.step_size, Dimension::TIME), adaptive_step.rejected_steps));
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Force Law: ");
+                    let active = force_laws.active_index();
+                    egui::ComboBox::from_id_source("force_law")
+                        .selected_text(force_laws.active().name())
+                        .show_ui(ui, |ui| {
+                            for (index, name) in force_laws.names() {
+                                if ui.selectable_label(index == active, name).clicked() {
+                                    force_laws.select(index);
+                                }
+                            }
                         });
-                        ui.horizontal(|ui| {
-                            ui.label("Current Acceleration: ");
-                            ui.add_enabled(false, 
-                                DragValue::new(&mut planet.acceleration.x)
-                                .suffix("x:")
-                            );
-                            ui.add_enabled(false, 
-                                DragValue::new(&mut planet.acceleration.y)
-                                .suffix("y:")
-                            );
+                });
+                for (name, mut value, dimension) in force_laws.active().parameters() {
+                    ui.horizontal(|ui| {
+                        ui.label(format!("{name}: "));
+                        if unit_drag_value(ui, true, &mut value, dimension, &units, "") {
+                            force_laws.active_mut().set_parameter(name, value);
+                        }
+                    });
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Forces: ");
+                    egui::ComboBox::from_id_source("force_solver")
+                        .selected_text(game.force_solver.name())
+                        .show_ui(ui, |ui| {
+                            for force_solver in ForceSolver::ALL {
+                                ui.selectable_value(&mut game.force_solver, force_solver, force_solver.name());
+                            }
                         });
-                        ui.separator();
+                });
+                if game.force_solver == ForceSolver::BarnesHut {
+                    ui.horizontal(|ui| {
+                        ui.label("Opening Angle: ");
+                        ui.add(
+                            DragValue::new(&mut game.opening_angle)
+                            .speed(0.01)
+                            .clamp_range(0.0..=2.0)
+                        );
                     });
-                }    
-            });
-                
-            ui.horizontal(|ui| {
-                ui.label("Game Speed: ");
-                ui.add_enabled(
-                    if game_state.get() == &GameState::Running {true} else {true},
-                    DragValue::new(&mut game.speed)
-                    .clamp_range(30.0..=1000.0)
-                );
+                }
+                ui.checkbox(&mut game.parallel, "Multithreaded");
+                ui.horizontal(|ui| {
+                    ui.label("Softening: ");
+                    if unit_drag_value(ui, true, &mut game.softening, Dimension::LENGTH, &units, "") {
+                        game.softening = game.softening.max(0.0);
+                    }
+                });
+                ui.checkbox(&mut game.post_newtonian, "Relativistic Correction (1PN)");
+                if game.post_newtonian {
+                    ui.horizontal(|ui| {
+                        ui.label("Speed of Light: ");
+                        if unit_drag_value(ui, true, &mut game.speed_of_light, Dimension::VELOCITY, &units, "") {
+                            game.speed_of_light = game.speed_of_light.max(1e-6);
+                        }
+                        if ui.button("Real").clicked() {
+                            game.speed_of_light = C / units.system.to_si(Dimension::VELOCITY);
+                        }
+                    });
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Collisions: ");
+                    egui::ComboBox::from_id_source("collision_mode")
+                        .selected_text(game.collision_mode.name())
+                        .show_ui(ui, |ui| {
+                            for collision_mode in CollisionMode::ALL {
+                                ui.selectable_value(&mut game.collision_mode, collision_mode, collision_mode.name());
+                            }
+                        });
+                });
+                if game.collision_mode == CollisionMode::Bounce {
+                    ui.horizontal(|ui| {
+                        ui.label("Restitution: ");
+                        ui.add(
+                            DragValue::new(&mut game.restitution)
+                            .speed(0.01)
+                            .clamp_range(0.0..=1.0)
+                        );
+                    });
+                }
+
+                ui.separator();
+                ui.label(RichText::new("Path Prediction (Tab)").color(ACCENT_COLOR));
+                ui.horizontal(|ui| {
+                    ui.label("Horizon: ");
+                    if unit_drag_value(ui, true, &mut game.prediction.horizon, Dimension::TIME, &units, "") {
+                        game.prediction.horizon = game.prediction.horizon.max(0.0);
+                    }
+                    // the horizon can also be set in steps
+                    let stepsize = game.stepsize;
+                    let mut steps = game.prediction.steps(stepsize);
+                    if ui.add(DragValue::new(&mut steps).clamp_range(0..=MAX_PREDICTION_STEPS).suffix(" steps")).changed() {
+                        game.prediction.horizon = steps as f64 * stepsize;
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Relative to: ");
+                    reference_frame_combo(ui, "path_frame", &mut game.path_frame, &planet_names);
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Resolution: one point every ");
+                    ui.add(DragValue::new(&mut game.prediction.sample_interval).clamp_range(1..=1000));
+                    ui.label(" steps");
+                });
+                ui.horizontal(|ui| {
+                    // collisions always get marked, close approaches only below this distance
+                    ui.label("Mark Close Approaches below: ");
+                    if unit_drag_value(ui, true, &mut game.prediction.close_approach_distance, Dimension::LENGTH, &units, "") {
+                        game.prediction.close_approach_distance = game.prediction.close_approach_distance.max(0.0);
+                    }
+                });
+
+                ui.separator();
+                if ui.button("Solar System Editor").clicked() {
+                    editor.open = !editor.open;
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Scenario: ");
+                    ui.text_edit_singleline(&mut scenario_file.path);
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Save").clicked() {
+                        scenario_file.request = Some(ScenarioRequest::Save);
+                    }
+                    // replaces all planets and particles
+                    if ui.button("Load").clicked() {
+                        scenario_file.request = Some(ScenarioRequest::Load);
+                    }
+                });
+                if !scenario_file.message.is_empty() {
+                    ui.label(scenario_file.message.as_str());
+                }
+                ui.label(RichText::new("Spawn (right click)").color(ACCENT_COLOR));
+                ui.horizontal(|ui| {
+                    ui.label("Velocity: ");
+                    egui::ComboBox::from_id_source("spawn_mode")
+                        .selected_text(game.spawn_mode.name())
+                        .show_ui(ui, |ui| {
+                            for spawn_mode in SpawnMode::ALL {
+                                ui.selectable_value(&mut game.spawn_mode, spawn_mode, spawn_mode.name());
+                            }
+                        });
+                });
+                if game.spawn_mode == SpawnMode::Orbit {
+                    ui.horizontal(|ui| {
+                        ui.label("Primary: ");
+                        orbit_primary_combo(ui, "orbit_primary", &mut game.orbit_primary, &planet_names);
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Eccentricity: ");
+                        ui.add(
+                            DragValue::new(&mut game.orbit_shape.eccentricity)
+                            .speed(0.01)
+                            .clamp_range(0.0..=0.99)
+                        );
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Direction: ");
+                        egui::ComboBox::from_id_source("orbit_direction")
+                            .selected_text(game.orbit_shape.direction.name())
+                            .show_ui(ui, |ui| {
+                                for direction in OrbitDirection::ALL {
+                                    ui.selectable_value(&mut game.orbit_shape.direction, direction, direction.name());
+                                }
+                            });
+                    });
+                    ui.horizontal(|ui| {
+                        // [Note: an inclined orbit leaves the xy plane, in 2D only its projection is visible]
+                        ui.label("Inclination: ");
+                        let mut degrees = game.orbit_shape.inclination.to_degrees();
+                        if ui.add(DragValue::new(&mut degrees).speed(1.0).clamp_range(0.0..=90.0).suffix("°")).changed() {
+                            game.orbit_shape.inclination = degrees.to_radians();
+                        }
+                    });
+                }
+
+                ui.separator();
+                ui.label(RichText::new(format!("Test Particles ({})", particle_q.iter().len())).color(ACCENT_COLOR));
+                ui.horizontal(|ui| {
+                    ui.label("Belt around: ");
+                    orbit_primary_combo(ui, "belt_primary", &mut game.belt_primary, &planet_names);
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Count: ");
+                    ui.add(
+                        DragValue::new(&mut game.belt.count)
+                        .speed(100.0)
+                        .clamp_range(1..=50000)
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Radius: ");
+                    let mut belt = game.belt;
+                    unit_drag_value(ui, true, &mut belt.inner_radius, Dimension::LENGTH, &units, "from ");
+                    unit_drag_value(ui, true, &mut belt.outer_radius, Dimension::LENGTH, &units, "to ");
+                    belt.inner_radius = belt.inner_radius.max(0.0);
+                    belt.outer_radius = belt.outer_radius.max(belt.inner_radius);
+                    game.belt = belt;
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Add Belt").clicked() {
+                        let planets = planet_q.iter().map(|(entity, planet, _)| (entity, planet)).collect::<Vec<_>>();
+                        if let Some(primary) = orbit_primary(&planets, game.belt_primary, None) {
+                            spawn_belt(&mut commands, game.belt, primary, gravitational_constant);
+                        }
+                    }
+                    if ui.button("Remove All").clicked() {
+                        for entity in particle_q.iter() {
+                            commands.entity(entity).despawn();
+                        }
+                    }
+                });
+
+                ui.separator();
+                ui.label(RichText::new("Diagnostics").color(ACCENT_COLOR));
+                let current = &diagnostics.current;
+                ui.label(format!("Kinetic Energy: {}", units.format(current.kinetic_energy, Dimension::ENERGY)));
+                ui.label(format!("Potential Energy: {}", units.format(current.potential_energy, Dimension::ENERGY)));
+                ui.label(format!("Total Energy: {} (drift: {:.2e})", units.format(current.total_energy(), Dimension::ENERGY), diagnostics.energy_drift));
+                ui.label(format!("Momentum: {} (drift: {:.2e})", units.format(current.momentum.length(), Dimension::MOMENTUM), diagnostics.momentum_drift));
+                ui.label(format!("Angular Momentum: {} (drift: {:.2e})", units.format(current.angular_momentum.length(), Dimension::ANGULAR_MOMENTUM), diagnostics.angular_momentum_drift));
+
+                ui.separator();
+                ui.label(RichText::new("Units").color(ACCENT_COLOR));
+                ui.horizontal(|ui| {
+                    // [Note: the planets keep their values in simulation units, so switching this changes what they mean physically]
+                    ui.label("Simulation: ");
+                    egui::ComboBox::from_id_source("unit_system")
+                        .selected_text(game.units.system.name())
+                        .show_ui(ui, |ui| {
+                            for system in UnitSystem::ALL {
+                                ui.selectable_value(&mut game.units.system, system, system.name());
+                            }
+                        });
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Display: ");
+                    egui::ComboBox::from_id_source("length_unit")
+                        .selected_text(game.units.display.length.symbol())
+                        .show_ui(ui, |ui| {
+                            for unit in LengthUnit::ALL {
+                                ui.selectable_value(&mut game.units.display.length, unit, unit.symbol());
+                            }
+                        });
+                    egui::ComboBox::from_id_source("mass_unit")
+                        .selected_text(game.units.display.mass.symbol())
+                        .show_ui(ui, |ui| {
+                            for unit in MassUnit::ALL {
+                                ui.selectable_value(&mut game.units.display.mass, unit, unit.symbol());
+                            }
+                        });
+                    egui::ComboBox::from_id_source("time_unit")
+                        .selected_text(game.units.display.time.symbol())
+                        .show_ui(ui, |ui| {
+                            for unit in TimeUnit::ALL {
+                                ui.selectable_value(&mut game.units.display.time, unit, unit.symbol());
+                            }
+                        });
+                });
             });
         });
 }
\ No newline at end of file
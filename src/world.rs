@@ -1,6 +1,9 @@
-use bevy::{input::mouse::{MouseScrollUnit, MouseWheel}, prelude::*, window::{PresentMode, PrimaryWindow}};
-use bevy_inspector_egui::{inspector_options::ReflectInspectorOptions, InspectorOptions};
+use bevy::{input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel}, prelude::*, window::{PresentMode, PrimaryWindow}};
+use bevy_inspector_egui::{bevy_egui::EguiContext, inspector_options::ReflectInspectorOptions, InspectorOptions};
 use iyes_perf_ui::{diagnostics::{PerfUiEntryFPS, PerfUiEntryFPSWorst}, PerfUiRoot};
+use serde::{Deserialize, Serialize};
+
+use crate::{diagnostics::{PerfUiEntryAngularMomentumDrift, PerfUiEntryEnergyDrift, PerfUiEntryMomentumDrift}, particles::BeltSettings, planets::PredictionSettings, physics::{CollisionMode, ForceSolver, Integrator, OrbitShape, Units, C}};
 
 
 
@@ -10,7 +13,7 @@ impl Plugin for WorldPlugin {
     fn build(&self, app: &mut App) {
         app
         .add_systems(Startup, (load_background, setup))
-        .add_systems(Update, (camera_zoom, update_fixed_timestep))
+        .add_systems(Update, (camera_zoom, orbit_camera_controls, apply_view_mode, update_fixed_timestep))
         // register Type Planet and Game so they are editable in the WorldInspector
         .register_type::<Game>()
         // load the ResourceInspectorPlugin in order to edit resources in the WorldInspector
@@ -18,7 +21,7 @@ impl Plugin for WorldPlugin {
         // initialize resource Game in order to be able to edit it in the WorldInspector
         .init_resource::<Game>()
         // create resource Game with speed set to 1.0
-        .insert_resource(Game{speed: 1.0, stepsize: 1.0/64.0, planet_count: 0})
+        .insert_resource(Game{speed: 1.0, stepsize: 1.0/64.0, planet_count: 0, tolerance: 1e-5, opening_angle: 0.5, parallel: true, softening: 1.0, restitution: 1.0, speed_of_light: C, ..default()})
         ;
     }
 }
@@ -26,6 +29,24 @@ impl Plugin for WorldPlugin {
 #[derive(Component)]
 pub struct MyCamera;
 
+// ############# Orbit Camera Component #############
+// 3D camera that looks at focus from the given distance, yaw (around z) and pitch (above the xy plane) in radians
+#[derive(Component)]
+pub struct OrbitCamera {
+    pub focus: Vec3,
+    pub distance: f32,
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl OrbitCamera {
+    fn transform(&self) -> Transform {
+        let offset = Vec3::new(self.pitch.cos() * self.yaw.cos(), self.pitch.cos() * self.yaw.sin(), self.pitch.sin()) * self.distance;
+        // z is up, so the xy plane (where the planets start) looks like the 2D view seen at an angle
+        Transform::from_translation(self.focus + offset).looking_at(self.focus, Vec3::Z)
+    }
+}
+
 #[derive(Component)]
 struct Background;
 
@@ -35,14 +56,115 @@ pub enum GameState {
     Running,
 }
 
-#[derive(Reflect, Resource, Default, InspectorOptions)]
+#[derive(Reflect, Serialize, Deserialize, Resource, Clone, Default, InspectorOptions)]
 #[reflect(Resource, InspectorOptions)]
 pub struct Game {
     pub speed: f32,
-    pub stepsize: f32,
+    pub stepsize: f64,
     pub planet_count: u32,
+    // numerical method for the simulation (and the path prediction)
+    pub integrator: Integrator,
+    // allowed error per sub step for adaptive integrators
+    pub tolerance: f64,
+    // exact or tree (barnes-hut) forces; the opening angle trades accuracy for speed (0 is exact, around 0.5 is a good value)
+    pub force_solver: ForceSolver,
+    pub opening_angle: f64,
+    // split the force calculation between multiple threads
+    pub parallel: bool,
+    // plummer softening length: keeps the forces finite when planets get very close
+    pub softening: f64,
+    // first order post-newtonian correction (perihelion precession), with c in simulation units
+    // [Note: the real speed of light is far too fast for the velocities on the screen, it has to be made smaller to see anything]
+    pub post_newtonian: bool,
+    pub speed_of_light: f64,
+    // what happens when planets touch
+    pub collision_mode: CollisionMode,
+    // only for CollisionMode::Bounce: 1 is a perfectly elastic bounce, 0 means the planets dont separate again
+    pub restitution: f64,
+    // what the simulation values mean physically, and in which units they get displayed
+    pub units: Units,
+    // which velocity planets get that are spawned with a right click
+    pub spawn_mode: SpawnMode,
+    // only for SpawnMode::Orbit: what the new planet orbits and how
+    pub orbit_primary: OrbitPrimary,
+    pub orbit_shape: OrbitShape,
+    // what the orbital elements in the UI are relative to
+    pub elements_primary: OrbitPrimary,
+    // belts of test particles get spawned around this primary
+    pub belt_primary: OrbitPrimary,
+    pub belt: BeltSettings,
+    // top-down 2D view or perspective 3D view
+    pub view_mode: ViewMode,
+    // how far and how detailed the paths of the planets get predicted
+    pub prediction: PredictionSettings,
+    // what the predicted paths get drawn relative to
+    pub path_frame: ReferenceFrame,
 }
 
+// ############# View Mode #############
+#[derive(Reflect, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ViewMode {
+    // the xy plane seen from above (z gets ignored for drawing), with sprites
+    #[default]
+    TwoD,
+    // perspective camera that orbits around a point, with spheres
+    ThreeD,
+}
+
+impl ViewMode {
+    pub const ALL: [ViewMode; 2] = [ViewMode::TwoD, ViewMode::ThreeD];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ViewMode::TwoD => "2D",
+            ViewMode::ThreeD => "3D",
+        }
+    }
+}
+
+// ############# Spawn Mode #############
+#[derive(Reflect, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SpawnMode {
+    // new planets dont move
+    #[default]
+    AtRest,
+    // new planets get the velocity for an orbit around the OrbitPrimary
+    Orbit,
+}
+
+impl SpawnMode {
+    pub const ALL: [SpawnMode; 2] = [SpawnMode::AtRest, SpawnMode::Orbit];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SpawnMode::AtRest => "At Rest",
+            SpawnMode::Orbit => "Orbit",
+        }
+    }
+}
+
+// what a new planet orbits in SpawnMode::Orbit (or what the orbital elements are relative to)
+#[derive(Reflect, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OrbitPrimary {
+    // center of mass of all (other) planets
+    #[default]
+    Barycenter,
+    // a single planet (if it doesnt exist anymore, the barycenter gets used)
+    Planet(Entity),
+}
+
+// what the predicted paths get drawn relative to: the path of the reference gets subtracted from all others,
+// so e.g. the path of a moon relative to its planet is a closed orbit around the planet (instead of a wobbly line following it)
+#[derive(Reflect, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReferenceFrame {
+    // absolute positions
+    #[default]
+    Absolute,
+    // center of mass of all planets
+    Barycenter,
+    // a single planet (if it doesnt exist anymore, the absolute positions get used)
+    Planet(Entity),
+}
 
 fn update_fixed_timestep(
     mut time_fixed: ResMut<Time<Fixed>>,
@@ -82,6 +204,40 @@ fn setup(
         )
     );
 
+    // spawn 3D Camera, only active in ViewMode::ThreeD
+    let orbit_camera = OrbitCamera {
+        focus: Vec3::ZERO,
+        // about the same area as the 2D camera shows
+        distance: 1500.0,
+        yaw: -std::f32::consts::FRAC_PI_2,
+        pitch: 1.0,
+    };
+    commands.spawn(
+        (
+            Camera3dBundle {
+                camera: Camera {
+                    is_active: false,
+                    ..default()
+                },
+                // the distances in the simulation are large, so the far plane has to be far away
+                projection: Projection::Perspective(PerspectiveProjection {
+                    far: 1e7,
+                    ..default()
+                }),
+                transform: orbit_camera.transform(),
+                ..default()
+            },
+            orbit_camera,
+            Name::new("3D Camera"),
+        )
+    );
+    // light for the planet spheres (the sprites in 2D dont need any)
+    commands.spawn(DirectionalLightBundle {
+        transform: Transform::from_xyz(1.0, -1.0, 2.0).looking_at(Vec3::ZERO, Vec3::Z),
+        ..default()
+    });
+    commands.insert_resource(AmbientLight { color: Color::WHITE, brightness: 300.0 });
+
     // spawn FPS Display []
     commands.spawn((
         PerfUiRoot {
@@ -95,6 +251,10 @@ fn setup(
         PerfUiEntryFPSWorst::default(),
         // add entry for the average FPS
         PerfUiEntryFPS::default(),
+        // add entries for the drift of the conserved quantities
+        PerfUiEntryEnergyDrift::default(),
+        PerfUiEntryMomentumDrift::default(),
+        PerfUiEntryAngularMomentumDrift::default(),
     ));
 
 }
@@ -102,7 +262,13 @@ fn setup(
 fn camera_zoom(
     mut query_camera: Query<&mut OrthographicProjection, With<Camera>>,
     mut scroll_evr: EventReader<MouseWheel>,
+    game: Res<Game>,
 ) {
+    // the 3D camera zooms in orbit_camera_controls
+    if game.view_mode == ViewMode::ThreeD {
+        scroll_evr.clear();
+        return;
+    }
     let mut projection = query_camera.single_mut();
 
     for ev in scroll_evr.read() {
@@ -124,6 +290,58 @@ fn camera_zoom(
     }
 }
 
+// left mouse button rotates, middle mouse button moves the focus, scrolling zooms (only in ViewMode::ThreeD)
+fn orbit_camera_controls(
+    mut camera_q: Query<(&mut OrbitCamera, &mut Transform)>,
+    mut motion_evr: EventReader<MouseMotion>,
+    mut scroll_evr: EventReader<MouseWheel>,
+    buttons: Res<ButtonInput<MouseButton>>,
+    mut egui_ctx: Query<&mut EguiContext, With<PrimaryWindow>>,
+    game: Res<Game>,
+) {
+    let motion = motion_evr.read().map(|ev| ev.delta).sum::<Vec2>();
+    let scroll = scroll_evr.read().map(|ev| ev.y.signum()).sum::<f32>();
+    // dont move the camera while the mouse is used for the UI
+    let over_ui = egui_ctx.get_single_mut().map(|mut ctx| ctx.get_mut().is_pointer_over_area()).unwrap_or(false);
+    if game.view_mode != ViewMode::ThreeD || over_ui {
+        return;
+    }
+    let Ok((mut camera, mut transform)) = camera_q.get_single_mut() else {
+        return;
+    };
+
+    if buttons.pressed(MouseButton::Left) {
+        camera.yaw -= motion.x * 0.005;
+        // looking straight down (or up) the up direction would be undefined
+        camera.pitch = (camera.pitch + motion.y * 0.005).clamp(-1.55, 1.55);
+    }
+    if buttons.pressed(MouseButton::Middle) {
+        // the focus moves with the mouse, faster when zoomed out
+        let speed = camera.distance * 0.0015;
+        camera.focus += (transform.left() * motion.x + transform.up() * motion.y) * speed;
+    }
+    // same steps as the 2D zoom
+    camera.distance *= 1.25f32.powf(-scroll);
+    *transform = camera.transform();
+}
+
+// only the camera of the current view mode renders
+fn apply_view_mode(
+    game: Res<Game>,
+    mut camera_2d_q: Query<&mut Camera, (With<MyCamera>, Without<OrbitCamera>)>,
+    mut camera_3d_q: Query<&mut Camera, (With<OrbitCamera>, Without<MyCamera>)>,
+) {
+    if !game.is_changed() {
+        return;
+    }
+    for mut camera in camera_2d_q.iter_mut() {
+        camera.is_active = game.view_mode == ViewMode::TwoD;
+    }
+    for mut camera in camera_3d_q.iter_mut() {
+        camera.is_active = game.view_mode == ViewMode::ThreeD;
+    }
+}
+
 fn load_background(
     // load the background image
     asset_server: Res<AssetServer>, 